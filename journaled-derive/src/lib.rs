@@ -0,0 +1,395 @@
+//! `#[derive(JournalEntry)]`, re-exported by `journaled` behind its `derive` feature.
+
+const FIELD_LEN_MAX: usize = 64;
+const MESSAGE: &str = "MESSAGE";
+const MESSAGE_ID: &str = "MESSAGE_ID";
+const PRIORITY: &str = "PRIORITY";
+
+/// Mirrors `journaled::raw::is_valid_field`, so that names are rejected at compile time.
+fn is_valid_field(field: &str) -> bool {
+    if field.len() > FIELD_LEN_MAX {
+        return false;
+    }
+
+    match field.as_bytes().split_first() {
+        Some((first, rest)) => {
+            first.is_ascii_uppercase()
+                && rest
+                    .iter()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == &b'_')
+        }
+        None => false,
+    }
+}
+
+/// Whether an id is 32 hex digits or a UUID, as accepted by `journaled::id128::Id128::parse`.
+fn is_valid_id(id: &str) -> bool {
+    let digits: Vec<_> = match id.len() {
+        32 => id.chars().collect(),
+        36 => id
+            .char_indices()
+            .filter(|(n, c)| !matches!((n, c), (8 | 13 | 18 | 23, '-')))
+            .map(|(_, c)| c)
+            .collect(),
+        _ => return false,
+    };
+    digits.len() == 32 && digits.iter().all(char::is_ascii_hexdigit)
+}
+
+/// The variant of `journaled::raw::Priority` for a syslog name or number.
+fn priority(name: &str) -> Option<&'static str> {
+    let variant = match name.to_ascii_lowercase().as_str() {
+        "0" | "emerg" | "emergency" | "panic" => "Emergency",
+        "1" | "alert" => "Alert",
+        "2" | "crit" | "critical" => "Critical",
+        "3" | "err" | "error" => "Error",
+        "4" | "warning" | "warn" => "Warning",
+        "5" | "notice" => "Notice",
+        "6" | "info" => "Info",
+        "7" | "debug" => "Debug",
+        _ => return None,
+    };
+    Some(variant)
+}
+
+/// The names of the fields referenced by `{name}` or `{name:spec}` in a message template.
+fn template_names(template: &str) -> Result<Vec<String>, &'static str> {
+    let mut names = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut spec = false;
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(':') => spec = true,
+                        Some(c) if !spec => name.push(c),
+                        Some(_) => (),
+                        None => return Err("unterminated `{` in message"),
+                    }
+                }
+                if name.is_empty() {
+                    return Err("message placeholders must name a field, e.g. `{path}`");
+                }
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '}' => return Err("unmatched `}` in message"),
+            _ => (),
+        }
+    }
+    Ok(names)
+}
+
+/// The attributes given on the struct.
+#[derive(Default)]
+struct EntryAttrs {
+    message_id: Option<syn::LitStr>,
+    priority: Option<syn::Ident>,
+    message: Option<syn::LitStr>,
+}
+
+/// The attributes given on a field.
+#[derive(Default)]
+struct FieldAttrs {
+    name: Option<syn::LitStr>,
+    skip: bool,
+}
+
+fn parse_entry_attrs(attrs: &[syn::Attribute]) -> syn::Result<EntryAttrs> {
+    let mut parsed = EntryAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("journal")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("message_id") {
+                let id: syn::LitStr = meta.value()?.parse()?;
+                if !is_valid_id(&id.value()) {
+                    return Err(syn::Error::new(
+                        id.span(),
+                        "invalid message id, expected 32 hex digits or a UUID",
+                    ));
+                }
+                parsed.message_id = Some(id);
+            } else if meta.path.is_ident("priority") {
+                let name: syn::LitStr = meta.value()?.parse()?;
+                let variant = priority(&name.value()).ok_or_else(|| {
+                    syn::Error::new(
+                        name.span(),
+                        "expected a priority from 0 to 7 or a syslog level name",
+                    )
+                })?;
+                parsed.priority = Some(syn::Ident::new(variant, name.span()));
+            } else if meta.path.is_ident("message") {
+                parsed.message = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `message_id`, `priority`, or `message`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+fn parse_field_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut parsed = FieldAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("journal")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                let name: syn::LitStr = meta.value()?.parse()?;
+                if !is_valid_field(&name.value()) {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "invalid field name, expected up to 64 of A-Z, 0-9 and _, starting \
+                         with A-Z",
+                    ));
+                }
+                parsed.name = Some(name);
+            } else if meta.path.is_ident("skip") {
+                parsed.skip = true;
+            } else {
+                return Err(meta.error("expected `name` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+/// Sends a struct as a journal entry, with a field for each of its fields.
+///
+/// Each field is named after its identifier in upper case, unless it's renamed with
+/// `#[journal(name = "FIELD")]` or left out with `#[journal(skip)]`.  Field types implement
+/// `journaled::entry::FieldValue`, and `Option` fields are left out when they're `None`.  The
+/// struct may set a `MESSAGE_ID`, a `PRIORITY`, and a `MESSAGE` formatted from its fields:
+///
+/// ```ignore
+/// #[derive(journaled::entry::JournalEntry)]
+/// #[journal(
+///     message_id = "6bb4d5e81ea14a3b9a4e77d8f1b3bf0e",
+///     priority = "warning",
+///     message = "Request to {path} failed with {status}"
+/// )]
+/// struct RequestFailed<'a> {
+///     path: &'a str,
+///     status: u16,
+///     #[journal(name = "REQUEST_ID")]
+///     id: Option<u64>,
+/// }
+/// ```
+///
+/// Field names, ids and priorities are checked at compile time, and fields can't be named after
+/// the `MESSAGE_ID`, `PRIORITY` or `MESSAGE` that the struct sets.  The fields are sent as they're
+/// borrowed, and can be copied into an `Entry` with `JournalEntry::to_entry`.
+#[proc_macro_derive(JournalEntry, attributes(journal))]
+pub fn derive_journal_entry(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "JournalEntry can only be derived for structs with named fields",
+            ))
+        }
+    };
+    let attrs = parse_entry_attrs(&input.attrs)?;
+
+    let mut values = Vec::new();
+    let mut bindings = Vec::new();
+    for (n, field) in fields.iter().enumerate() {
+        let field_attrs = parse_field_attrs(&field.attrs)?;
+        if field_attrs.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("fields are named");
+        let name = match &field_attrs.name {
+            Some(name) => name.value(),
+            None => {
+                let name = ident
+                    .to_string()
+                    .trim_start_matches("r#")
+                    .to_ascii_uppercase();
+                if !is_valid_field(&name) {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "field name is not valid for the journal, rename it with \
+                         `#[journal(name = \"FIELD\")]`",
+                    ));
+                }
+                name
+            }
+        };
+        let set_by = match name.as_str() {
+            MESSAGE if attrs.message.is_some() => Some("message"),
+            MESSAGE_ID if attrs.message_id.is_some() => Some("message_id"),
+            PRIORITY if attrs.priority.is_some() => Some("priority"),
+            _ => None,
+        };
+        if let Some(attr) = set_by {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("{} is already set by `#[journal({} = ...)]`", name, attr),
+            ));
+        }
+
+        let binding = quote::format_ident!("__value_{}", n);
+        bindings.push(quote::quote! {
+            let #binding = ::journaled::entry::FieldValue::field_value(&self.#ident);
+        });
+        values.push(quote::quote! {
+            #binding.as_ref().map(|value| {
+                (
+                    ::journaled::raw::Field::unchecked(#name),
+                    ::core::convert::AsRef::<[u8]>::as_ref(value),
+                )
+            })
+        });
+    }
+
+    let mut header = Vec::new();
+    if let Some(id) = &attrs.message_id {
+        header.push(quote::quote! {
+            {
+                const MESSAGE_ID: ::journaled::id128::MessageId =
+                    ::journaled::id128::MessageId::parse_const(#id);
+                ::core::option::Option::Some((
+                    ::journaled::raw::MESSAGE_ID,
+                    MESSAGE_ID.as_str().as_bytes(),
+                ))
+            }
+        });
+    }
+    if let Some(priority) = &attrs.priority {
+        header.push(quote::quote! {
+            ::core::option::Option::Some((
+                ::journaled::raw::PRIORITY,
+                ::journaled::raw::Priority::#priority.as_str().as_bytes(),
+            ))
+        });
+    }
+    if let Some(template) = &attrs.message {
+        let names = template_names(&template.value())
+            .map_err(|msg| syn::Error::new(template.span(), msg))?;
+        let mut args = Vec::new();
+        for name in names {
+            let field = fields
+                .iter()
+                .filter_map(|field| field.ident.as_ref())
+                .find(|ident| *ident == name.as_str())
+                .ok_or_else(|| {
+                    syn::Error::new(template.span(), format!("no field named `{}`", name))
+                })?;
+            args.push(quote::quote! { #field = self.#field });
+        }
+        bindings.push(quote::quote! {
+            let __message =
+                ::journaled::entry::Message::new(::core::format_args!(#template #(, #args)*));
+        });
+        header.push(quote::quote! {
+            ::core::option::Option::Some((
+                ::journaled::raw::MESSAGE,
+                ::core::convert::AsRef::<[u8]>::as_ref(&__message),
+            ))
+        });
+    }
+
+    let len = header.len() + values.len();
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote::quote! {
+        impl #impl_generics ::journaled::entry::JournalEntry for #ident #ty_generics
+        #where_clause
+        {
+            fn send(
+                &self,
+                writer: &::journaled::raw::JournalWriter,
+            ) -> ::std::io::Result<()> {
+                #(#bindings)*
+                let values: [::core::option::Option<(::journaled::raw::Field<'static>, &[u8])>;
+                    #len] = [#(#header,)* #(#values,)*];
+                writer.send_bytes(values.into_iter().flatten())
+            }
+
+            fn to_entry(&self) -> ::journaled::entry::Entry {
+                #(#bindings)*
+                let values: [::core::option::Option<(::journaled::raw::Field<'static>, &[u8])>;
+                    #len] = [#(#header,)* #(#values,)*];
+                values.into_iter().flatten().collect()
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_field() {
+        assert!(is_valid_field("REQUEST_ID"));
+        assert!(!is_valid_field("_PID"));
+        assert!(!is_valid_field("0ID"));
+        assert!(!is_valid_field(&"A".repeat(65)));
+    }
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id("6bb4d5e81ea14a3b9a4e77d8f1b3bf0e"));
+        assert!(is_valid_id("6bb4d5e8-1ea1-4a3b-9a4e-77d8f1b3bf0e"));
+        assert!(!is_valid_id("6bb4d5e81ea14a3b9a4e77d8f1b3bf0g"));
+        assert!(!is_valid_id("6bb4d5e8-1ea14a3b-9a4e-77d8f1b3bf0e"));
+    }
+
+    #[test]
+    fn test_template_names() {
+        assert_eq!(
+            template_names("{path} failed with {status:>3} {{literal}} {path}"),
+            Ok(vec!["path".to_owned(), "status".to_owned()])
+        );
+        assert!(template_names("{}").is_err());
+        assert!(template_names("{path").is_err());
+        assert!(template_names("path}").is_err());
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let error = |input: &str| {
+            expand(syn::parse_str(input).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(error("struct A(u8);").contains("named fields"));
+        assert!(error("#[journal(priority = \"loud\")] struct A { a: u8 }").contains("priority"));
+        assert!(error("#[journal(message_id = \"1\")] struct A { a: u8 }").contains("id"));
+        assert!(error("struct A { #[journal(name = \"lower\")] a: u8 }").contains("field name"));
+        assert!(error("#[journal(message = \"{b}\")] struct A { a: u8 }").contains("`b`"));
+        assert!(error("#[journal(message = \"a\")] struct A { message: u8 }").contains("MESSAGE"));
+        assert!(error(
+            "#[journal(message_id = \"6bb4d5e81ea14a3b9a4e77d8f1b3bf0e\")] \
+             struct A { message_id: u8 }"
+        )
+        .contains("MESSAGE_ID"));
+        assert!(error(
+            "#[journal(priority = \"info\")] struct A { #[journal(name = \"PRIORITY\")] p: u8 }"
+        )
+        .contains("PRIORITY"));
+    }
+}
@@ -0,0 +1,469 @@
+const ENTRY_PREFIX: &str = "-- ";
+const SUBJECT: &str = "Subject";
+const DEFINED_BY: &str = "Defined-By";
+const VARIABLE_DELIMITER: char = '@';
+
+/// The ways in which a catalog can fail to parse.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// Text appeared before the first `-- <id>` line.
+    MissingId,
+    /// An entry line did not contain a valid 128-bit id.
+    InvalidId,
+    /// A line in the header section was not of the form `Name: value`.
+    InvalidHeader,
+}
+
+/// Error returned when a catalog fails to parse, along with the 1-based line where it occurred.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::MissingId => "text before the first entry id",
+            ParseErrorKind::InvalidId => "invalid entry id",
+            ParseErrorKind::InvalidHeader => "expected a header of the form `Name: value`",
+        };
+        write!(f, "line {}: {}", self.line, reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// CatalogEntry is the explanation of a single message id, optionally for a specific language.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CatalogEntry {
+    id: crate::id128::Id128,
+    language: Option<String>,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl CatalogEntry {
+    pub fn new<S>(id: crate::id128::Id128, subject: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            id,
+            language: None,
+            headers: vec![(SUBJECT.to_owned(), subject.into())],
+            body: String::new(),
+        }
+    }
+
+    pub fn with_language<S>(mut self, language: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn with_defined_by<S>(self, defined_by: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.with_header(DEFINED_BY, defined_by)
+    }
+
+    /// Appends a header such as `Support` or `Documentation`, which may be repeated.
+    pub fn with_header<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body, which may reference fields of the entry as `@FIELD@`.
+    pub fn with_body<S>(mut self, body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.body = body.into();
+        finish(self)
+    }
+
+    pub fn id(&self) -> crate::id128::Id128 {
+        self.id
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Returns the first header with the given name, which is compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.header(SUBJECT)
+    }
+
+    pub fn defined_by(&self) -> Option<&str> {
+        self.header(DEFINED_BY)
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Renders the headers and body as `journalctl -x` would, substituting `@FIELD@` with the
+    /// values of the entry's fields.
+    ///
+    /// As with `sd_journal_get_catalog`, a field that isn't present is replaced by its name.
+    pub fn render<'a, I, V>(&self, fields: I) -> String
+    where
+        I: IntoIterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<[u8]>,
+    {
+        let fields: Vec<_> = fields.into_iter().collect();
+        let lookup = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field.as_str() == name)
+                .map(|(_, value)| String::from_utf8_lossy(value.as_ref()).into_owned())
+        };
+
+        let mut text = String::new();
+        for (name, value) in &self.headers {
+            text.push_str(name);
+            text.push_str(": ");
+            text.push_str(&substitute(value, lookup));
+            text.push('\n');
+        }
+        text.push('\n');
+        text.push_str(&substitute(&self.body, lookup));
+        text
+    }
+}
+
+impl std::fmt::Display for CatalogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", ENTRY_PREFIX, self.id)?;
+        if let Some(language) = &self.language {
+            write!(f, " {}", language)?;
+        }
+        writeln!(f)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        writeln!(f)?;
+        if !self.body.is_empty() {
+            writeln!(f, "{}", self.body.trim_end_matches('\n'))?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces `@FIELD@` references in `text` using `lookup`, leaving anything that isn't a valid
+/// field name untouched.
+fn substitute<F>(text: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(VARIABLE_DELIMITER) {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find(VARIABLE_DELIMITER) {
+            Some(end) if crate::raw::Field::validate(&after[..end]).is_some() => {
+                let name = &after[..end];
+                output.push_str(&lookup(name).unwrap_or_else(|| name.to_owned()));
+                rest = &after[end + 1..];
+            }
+            _ => {
+                output.push(VARIABLE_DELIMITER);
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Catalog is an ordered collection of entries, as stored in a single `.catalog` file.
+///
+/// A catalog explains entries with a given `MESSAGE_ID`, and is what `journalctl -x` displays
+/// next to a message.  Catalog files are installed into `/usr/lib/systemd/catalog/` and picked up
+/// by `journalctl --update-catalog`.  The format is described at <https://systemd.io/CATALOG/>.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Catalog {
+    entries: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the contents of a `.catalog` file.
+    ///
+    /// Lines starting with `#` are comments.  Each entry starts with `-- <id> [language]`,
+    /// followed by `Name: value` headers, an empty line, and then the body.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut entries = Vec::new();
+        let mut current: Option<CatalogEntry> = None;
+        let mut in_headers = false;
+        let mut previous_empty = true;
+
+        for (n, line) in text.lines().enumerate() {
+            let error = |kind| ParseError { line: n + 1, kind };
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            if previous_empty {
+                if let Some(header) = line.strip_prefix(ENTRY_PREFIX) {
+                    let mut parts = header.split_whitespace();
+                    let id = parts
+                        .next()
+                        .and_then(crate::id128::Id128::parse)
+                        .ok_or_else(|| error(ParseErrorKind::InvalidId))?;
+                    let language = parts.next().map(ToOwned::to_owned);
+                    if parts.next().is_some() {
+                        return Err(error(ParseErrorKind::InvalidId));
+                    }
+
+                    entries.extend(current.take().map(finish));
+                    current = Some(CatalogEntry {
+                        id,
+                        language,
+                        headers: Vec::new(),
+                        body: String::new(),
+                    });
+                    in_headers = true;
+                    previous_empty = false;
+                    continue;
+                }
+            }
+
+            previous_empty = line.trim().is_empty();
+            let entry = match current.as_mut() {
+                Some(entry) => entry,
+                None if previous_empty => continue,
+                None => return Err(error(ParseErrorKind::MissingId)),
+            };
+
+            if in_headers {
+                if previous_empty {
+                    in_headers = false;
+                } else {
+                    let (name, value) = line
+                        .split_once(':')
+                        .ok_or_else(|| error(ParseErrorKind::InvalidHeader))?;
+                    entry
+                        .headers
+                        .push((name.trim().to_owned(), value.trim().to_owned()));
+                }
+            } else {
+                entry.body.push_str(line);
+                entry.body.push('\n');
+            }
+        }
+        entries.extend(current.map(finish));
+
+        Ok(Self { entries })
+    }
+
+    pub fn push(&mut self, entry: CatalogEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    /// Finds the entry for an id, preferring one in the given language and otherwise falling
+    /// back to an entry without a language.
+    pub fn find(&self, id: crate::id128::Id128, language: Option<&str>) -> Option<&CatalogEntry> {
+        let mut matching = self.entries.iter().filter(|entry| entry.id == id);
+        match language {
+            Some(language) => matching
+                .clone()
+                .find(|entry| entry.language.as_deref() == Some(language))
+                .or_else(|| matching.find(|entry| entry.language.is_none())),
+            None => matching.find(|entry| entry.language.is_none()),
+        }
+    }
+
+    /// Writes the catalog in the `.catalog` format.
+    pub fn write_to<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        write!(writer, "{}", self)
+    }
+}
+
+impl std::fmt::Display for Catalog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (n, entry) in self.entries.iter().enumerate() {
+            if n > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Catalog {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Drops the trailing empty lines that separate a body from the next entry.
+fn finish(mut entry: CatalogEntry) -> CatalogEntry {
+    let len = entry.body.trim_end().len();
+    entry.body.truncate(len);
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = "# A comment
+
+-- fc2e22bc6ee647b6b90729ab34a250b1
+Subject: Process @COREDUMP_PID@ (@COREDUMP_COMM@) dumped core
+Defined-By: systemd
+Documentation: man:core(5)
+
+Process @COREDUMP_PID@ (@COREDUMP_COMM@) crashed and dumped core.
+
+This usually indicates a programming error.
+
+-- fc2e22bc6ee647b6b90729ab34a250b1 de
+Subject: Speicherabbild für Prozess @COREDUMP_PID@ (@COREDUMP_COMM) generiert
+
+Prozess @COREDUMP_PID@ (@COREDUMP_COMM@) ist abgebrochen worden.
+";
+
+    fn id() -> crate::id128::Id128 {
+        "fc2e22bc6ee647b6b90729ab34a250b1".parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let catalog = Catalog::parse(CATALOG).unwrap();
+        assert_eq!(catalog.entries().len(), 2);
+
+        let entry = &catalog.entries()[0];
+        assert_eq!(entry.id(), id());
+        assert_eq!(entry.language(), None);
+        assert_eq!(
+            entry.subject(),
+            Some("Process @COREDUMP_PID@ (@COREDUMP_COMM@) dumped core")
+        );
+        assert_eq!(entry.defined_by(), Some("systemd"));
+        assert_eq!(entry.header("documentation"), Some("man:core(5)"));
+        assert_eq!(
+            entry.body(),
+            "Process @COREDUMP_PID@ (@COREDUMP_COMM@) crashed and dumped core.\n\n\
+             This usually indicates a programming error."
+        );
+
+        assert_eq!(catalog.entries()[1].language(), Some("de"));
+        assert_eq!(catalog.find(id(), Some("de")), Some(&catalog.entries()[1]));
+        assert_eq!(catalog.find(id(), Some("fr")), Some(&catalog.entries()[0]));
+        assert_eq!(catalog.find(id(), None), Some(&catalog.entries()[0]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Catalog::parse("# comment\nSubject: x\n").unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert_eq!(err.kind(), &ParseErrorKind::MissingId);
+
+        let err = Catalog::parse("-- not-an-id\n").unwrap_err();
+        assert_eq!(err.kind(), &ParseErrorKind::InvalidId);
+
+        let err = Catalog::parse("-- fc2e22bc6ee647b6b90729ab34a250b1\nSubject\n").unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert_eq!(err.kind(), &ParseErrorKind::InvalidHeader);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let catalog = Catalog::parse(CATALOG).unwrap();
+        assert_eq!(Catalog::parse(&catalog.to_string()).unwrap(), catalog);
+
+        let mut built = Catalog::new();
+        built.push(
+            CatalogEntry::new(id(), "Something happened")
+                .with_defined_by("journaled")
+                .with_header("Documentation", "man:journaled(1)")
+                .with_body("It happened to @UNIT@.\n"),
+        );
+        assert_eq!(
+            built.to_string(),
+            "-- fc2e22bc6ee647b6b90729ab34a250b1
+Subject: Something happened
+Defined-By: journaled
+Documentation: man:journaled(1)
+
+It happened to @UNIT@.
+"
+        );
+        assert_eq!(Catalog::parse(&built.to_string()).unwrap(), built);
+    }
+
+    #[test]
+    fn test_render() {
+        let catalog = Catalog::parse(CATALOG).unwrap();
+        let pid = crate::raw::Field::validate("COREDUMP_PID").unwrap();
+
+        assert_eq!(
+            catalog.entries()[0].render([(pid, "42")]),
+            "Subject: Process 42 (COREDUMP_COMM) dumped core
+Defined-By: systemd
+Documentation: man:core(5)
+
+Process 42 (COREDUMP_COMM) crashed and dumped core.
+
+This usually indicates a programming error."
+        );
+    }
+
+    #[test]
+    fn test_substitute() {
+        let lookup = |name: &str| (name == "A").then(|| "1".to_owned());
+        assert_eq!(substitute("@A@ @B@", lookup), "1 B");
+        assert_eq!(
+            substitute("user@example.com @A", lookup),
+            "user@example.com @A"
+        );
+        assert_eq!(substitute("@@A@", lookup), "@1");
+    }
+}
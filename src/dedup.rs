@@ -0,0 +1,286 @@
+//! Collapsing of identical consecutive entries, as configured by
+//! [[`crate::raw::JournalWriter::with_dedup`]].
+//!
+//! A flapping check can send the same entry thousands of times.  Instead, the first is sent and
+//! the repeats are counted, then summarized by an entry with `MESSAGE` of `last message repeated
+//! N times` and `REPEAT_COUNT` of `N`, as syslog does.
+
+const REPEAT_COUNT: crate::raw::Field = crate::raw::Field::unchecked("REPEAT_COUNT");
+
+crate::message_id! {
+    /// Identifies the summaries of entries that were collapsed by [[`Dedup`]].
+    pub REPEATED = "8773e8ddbc0d436dac2e260a298dfabc";
+}
+
+/// Fields of the repeated entry that are copied into its summary.
+const SUMMARY_FIELDS: [crate::raw::Field; 3] = [
+    crate::raw::PRIORITY,
+    crate::raw::CODE_FILE,
+    crate::raw::CODE_LINE,
+];
+
+/// Dedup decides which entries are identical and how long repeats are collapsed for.
+///
+/// Entries are compared by a hash of their encoded fields, leaving out fields that change between
+/// otherwise identical entries, which are `TID` and `SYSLOG_TIMESTAMP` by default.  Repeats are
+/// collapsed for at most `window` after the first entry, so that a summary is sent at least that
+/// often while an entry keeps repeating.  Otherwise the summary is sent ahead of the next entry
+/// that's different, or by [[`crate::raw::JournalWriter::flush`]], which has to be called before
+/// exiting so that the last summary isn't lost.
+///
+/// ```no_run
+/// let dedup = journaled::dedup::Dedup::new(std::time::Duration::from_secs(60))
+///     .ignore(journaled::raw::Field::validate("REQUEST_ID").unwrap());
+/// let journal = journaled::raw::JournalWriter::new()
+///     .unwrap()
+///     .with_dedup(dedup);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dedup {
+    window: std::time::Duration,
+    volatile: Vec<crate::raw::OwnedField>,
+}
+
+impl Dedup {
+    pub fn new(window: std::time::Duration) -> Self {
+        Self {
+            window,
+            volatile: vec![crate::raw::TID.into(), crate::raw::SYSLOG_TIMESTAMP.into()],
+        }
+    }
+
+    /// Leaves a field out when comparing entries.
+    pub fn ignore(mut self, field: crate::raw::Field) -> Self {
+        self.volatile.push(field.into());
+        self
+    }
+
+    pub fn window(&self) -> std::time::Duration {
+        self.window
+    }
+
+    /// Hashes the encoded fields of an entry, other than the volatile ones.
+    fn hash<'a, I, V>(&self, values: I) -> u64
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<[u8]>,
+    {
+        use std::hash::Hasher;
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for (field, value) in values {
+            if self
+                .volatile
+                .iter()
+                .any(|volatile| field == volatile.into())
+            {
+                continue;
+            }
+            let mut encoded = Vec::new();
+            crate::raw::encode(&mut encoded, &field, value);
+            hasher.write(&encoded);
+        }
+        hasher.finish()
+    }
+}
+
+/// Last is the most recently sent entry and how many times it's been repeated since.
+#[derive(Debug)]
+struct Last {
+    hash: u64,
+    since: std::time::Instant,
+    repeats: u64,
+    fields: Vec<(crate::raw::Field<'static>, Vec<u8>)>,
+}
+
+/// Summary describes the repeats of an entry.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Summary {
+    message: String,
+    count: String,
+    fields: Vec<(crate::raw::Field<'static>, Vec<u8>)>,
+}
+
+impl Summary {
+    fn new(last: &Last) -> Self {
+        Self {
+            message: format!("last message repeated {} times", last.repeats),
+            count: last.repeats.to_string(),
+            fields: last.fields.clone(),
+        }
+    }
+
+    /// The fields of the summary entry, including the priority and call site of the entry that
+    /// was repeated.
+    pub(crate) fn values(
+        &self,
+    ) -> impl Iterator<Item = (crate::raw::Field<'static>, &[u8])> + Clone {
+        [
+            (crate::raw::MESSAGE_ID, REPEATED.as_str().as_bytes()),
+            (crate::raw::MESSAGE, self.message.as_bytes()),
+            (REPEAT_COUNT, self.count.as_bytes()),
+        ]
+        .into_iter()
+        .chain(
+            self.fields
+                .iter()
+                .map(|(field, value)| (*field, value.as_slice())),
+        )
+    }
+}
+
+/// Deduplicator keeps the last entry sent by a writer.
+#[derive(Debug)]
+pub(crate) struct Deduplicator {
+    dedup: Dedup,
+    last: std::sync::Mutex<Option<Last>>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(dedup: Dedup) -> Self {
+        Self {
+            dedup,
+            last: Default::default(),
+        }
+    }
+
+    /// Decides whether an entry should be sent, returning the summary of the previous entry's
+    /// repeats if it's no longer being collapsed.
+    pub(crate) fn check<'a, I, V>(
+        &self,
+        values: I,
+        now: std::time::Instant,
+    ) -> (bool, Option<Summary>)
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)> + Clone,
+        V: AsRef<[u8]>,
+    {
+        let hash = self.dedup.hash(values.clone());
+        let mut last = self.lock();
+
+        if let Some(last) = last.as_mut() {
+            if last.hash == hash && now.saturating_duration_since(last.since) < self.dedup.window {
+                last.repeats += 1;
+                return (false, None);
+            }
+        }
+
+        let summary = last
+            .as_ref()
+            .filter(|last| last.repeats > 0)
+            .map(Summary::new);
+        *last = Some(Last {
+            hash,
+            since: now,
+            repeats: 0,
+            fields: values
+                .filter_map(|(field, value)| {
+                    SUMMARY_FIELDS
+                        .iter()
+                        .find(|summary_field| **summary_field == field)
+                        .map(|summary_field| (*summary_field, value.as_ref().to_vec()))
+                })
+                .collect(),
+        });
+        (true, summary)
+    }
+
+    /// Takes the summary of the last entry's repeats, if there are any.
+    pub(crate) fn pending(&self) -> Option<Summary> {
+        let mut last = self.lock();
+        let last = last.as_mut().filter(|last| last.repeats > 0)?;
+        let summary = Summary::new(last);
+        last.repeats = 0;
+        Some(summary)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Last>> {
+        self.last.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(message: &str, tid: &str) -> [(crate::raw::Field<'static>, String); 4] {
+        [
+            (crate::raw::PRIORITY, "3".to_owned()),
+            (crate::raw::MESSAGE, message.to_owned()),
+            (crate::raw::TID, tid.to_owned()),
+            (crate::raw::CODE_LINE, "12".to_owned()),
+        ]
+    }
+
+    fn values(summary: &Summary) -> Vec<(&str, &[u8])> {
+        summary
+            .values()
+            .map(|(field, value)| (field.as_str(), value))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let dedup = Deduplicator::new(Dedup::new(std::time::Duration::from_secs(10)));
+        let start = std::time::Instant::now();
+        let check = |message, tid, secs| {
+            dedup.check(
+                entry(message, tid).into_iter(),
+                start + std::time::Duration::from_secs(secs),
+            )
+        };
+
+        assert_eq!(check("down", "1", 0), (true, None));
+        assert_eq!(check("down", "2", 1), (false, None));
+        assert_eq!(check("down", "1", 2), (false, None));
+
+        let (send, summary) = check("up", "1", 3);
+        assert!(send);
+        assert_eq!(
+            values(&summary.unwrap()),
+            [
+                ("MESSAGE_ID", REPEATED.as_str().as_bytes()),
+                ("MESSAGE", b"last message repeated 2 times"),
+                ("REPEAT_COUNT", b"2"),
+                ("PRIORITY", b"3"),
+                ("CODE_LINE", b"12"),
+            ]
+        );
+        assert_eq!(check("down", "1", 4), (true, None));
+    }
+
+    #[test]
+    fn test_window() {
+        let dedup = Deduplicator::new(Dedup::new(std::time::Duration::from_secs(10)));
+        let start = std::time::Instant::now();
+        let check = |secs| {
+            dedup.check(
+                entry("down", "1").into_iter(),
+                start + std::time::Duration::from_secs(secs),
+            )
+        };
+
+        assert_eq!(check(0), (true, None));
+        assert_eq!(check(5), (false, None));
+        let (send, summary) = check(10);
+        assert!(send);
+        assert_eq!(summary.unwrap().count, "1");
+        assert_eq!(check(11), (false, None));
+
+        assert_eq!(dedup.pending().unwrap().count, "1");
+        assert_eq!(dedup.pending(), None);
+    }
+
+    #[test]
+    fn test_ignore() {
+        let field = crate::raw::Field::validate("REQUEST_ID").unwrap();
+        let dedup = Dedup::new(std::time::Duration::from_secs(10));
+        let hash = |dedup: &Dedup, id| {
+            dedup.hash([(crate::raw::MESSAGE, "down"), (field, id)].into_iter())
+        };
+
+        assert_ne!(hash(&dedup, "1"), hash(&dedup, "2"));
+        let dedup = dedup.ignore(field);
+        assert_eq!(hash(&dedup, "1"), hash(&dedup, "2"));
+    }
+}
@@ -0,0 +1,454 @@
+/// Entry is an ordered list of fields and their values, for code that builds or inspects entries
+/// as a whole rather than sending fields as they're produced.
+///
+/// A field may appear more than once, e.g. several `TAG` values, in which case journald keeps
+/// every value.  The order of the values is kept, both within a field and across fields.
+///
+/// ```no_run
+/// let tag = journaled::raw::Field::validate("TAG").unwrap();
+/// let entry = journaled::entry::Entry::new()
+///     .with(journaled::raw::MESSAGE, "Hello World")
+///     .with(tag, "first")
+///     .with(tag, "second");
+/// assert_eq!(entry.get_all(tag).collect::<Vec<_>>(), [b"first".as_slice(), b"second"]);
+///
+/// let journal = journaled::raw::JournalWriter::new().unwrap();
+/// journal.send_bytes(entry.values()).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Entry {
+    fields: Vec<(crate::raw::OwnedField, Vec<u8>)>,
+}
+
+impl Entry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value for a field, after any values it already has.
+    pub fn with<F, V>(mut self, field: F, value: V) -> Self
+    where
+        F: Into<crate::raw::OwnedField>,
+        V: Into<Vec<u8>>,
+    {
+        self.push(field, value);
+        self
+    }
+
+    /// Adds a value for a field, after any values it already has.
+    pub fn push<F, V>(&mut self, field: F, value: V)
+    where
+        F: Into<crate::raw::OwnedField>,
+        V: Into<Vec<u8>>,
+    {
+        self.fields.push((field.into(), value.into()));
+    }
+
+    /// Removes every value of a field, returning them in order.
+    pub fn remove(&mut self, field: crate::raw::Field) -> Vec<Vec<u8>> {
+        let mut removed = Vec::new();
+        self.fields.retain_mut(|(other, value)| {
+            if crate::raw::Field::from(&*other) == field {
+                removed.push(std::mem::take(value));
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    /// The first value of a field.
+    pub fn get(&self, field: crate::raw::Field) -> Option<&[u8]> {
+        self.values()
+            .find(|(other, _)| *other == field)
+            .map(|(_, value)| value)
+    }
+
+    /// Every value of a field, in the order they were added.
+    pub fn get_all<'a>(
+        &'a self,
+        field: crate::raw::Field<'a>,
+    ) -> impl Iterator<Item = &'a [u8]> + Clone + 'a {
+        self.values()
+            .filter(move |(other, _)| *other == field)
+            .map(|(_, value)| value)
+    }
+
+    /// Each field that has a value, once, in the order they were first added.
+    pub fn fields(&self) -> impl Iterator<Item = crate::raw::Field<'_>> + Clone {
+        self.values()
+            .enumerate()
+            .filter(|(n, (field, _))| {
+                !self.fields[..*n]
+                    .iter()
+                    .any(|(other, _)| crate::raw::Field::from(other) == *field)
+            })
+            .map(|(_, (field, _))| field)
+    }
+
+    /// The number of values, counting each value of a repeated field.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Is used to construct the tuples to be passed into [[`crate::raw::JournalWriter`]], with
+    /// repeated fields once per value.
+    pub fn values(&self) -> impl Iterator<Item = (crate::raw::Field<'_>, &[u8])> + Clone {
+        self.fields
+            .iter()
+            .map(|(field, value)| (field.into(), value.as_slice()))
+    }
+}
+
+impl<F, V> std::iter::FromIterator<(F, V)> for Entry
+where
+    F: Into<crate::raw::OwnedField>,
+    V: Into<Vec<u8>>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (F, V)>,
+    {
+        let mut entry = Self::new();
+        entry.extend(iter);
+        entry
+    }
+}
+
+impl<F, V> std::iter::Extend<(F, V)> for Entry
+where
+    F: Into<crate::raw::OwnedField>,
+    V: Into<Vec<u8>>,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (F, V)>,
+    {
+        for (field, value) in iter {
+            self.push(field, value);
+        }
+    }
+}
+
+/// JournalEntry is a type that's sent as an entry of its own, usually through
+/// `#[derive(JournalEntry)]` behind the `derive` feature.
+pub trait JournalEntry {
+    fn send(&self, writer: &crate::raw::JournalWriter) -> std::io::Result<()>;
+
+    /// The fields that [[`JournalEntry::send`]] sends, copied into an [[`Entry`]], e.g. to check
+    /// them or to add to them before sending.
+    fn to_entry(&self) -> Entry;
+}
+
+#[cfg(feature = "derive")]
+pub use journaled_derive::JournalEntry;
+
+/// FieldValue is a type that can be the value of a field of a [[`JournalEntry`]].
+///
+/// Strings and bytes are borrowed, and other scalars are formatted on the stack by
+/// [[`Formatted`]], so a derived entry is sent without allocating for its fields.  A derived
+/// `MESSAGE` is formatted on the stack too, by [[`Message`]], unless it's too long.
+pub trait FieldValue {
+    type Bytes<'a>: AsRef<[u8]>
+    where
+        Self: 'a;
+
+    /// The value as it's sent, or `None` to leave the field out.
+    fn field_value(&self) -> Option<Self::Bytes<'_>>;
+}
+
+/// The longest text of the scalars other than floats, which is that of `i128::MIN`.
+const FORMATTED_LEN_MAX: usize = 40;
+
+/// The longest text of a float.  `Display` never uses an exponent, so the shortest digits that
+/// round-trip are padded with zeros, e.g. `-5e-324` is written with 323 zeros after the point.
+const FLOAT_LEN_MAX: usize = 330;
+
+/// Formatted is the text of a scalar, such as an integer, float, `bool`, `char` or id, held on the
+/// stack in a buffer of `N` bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct Formatted<const N: usize = FORMATTED_LEN_MAX> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Formatted<N> {
+    fn new(value: impl std::fmt::Display) -> Self {
+        use std::io::Write;
+
+        let mut buf = [0; N];
+        let mut remaining = buf.as_mut_slice();
+        write!(remaining, "{}", value).expect("scalars fit in the buffer");
+        let len = N - remaining.len();
+        Self { buf, len }
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for Formatted<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// The longest text that [[`Message`]] holds on the stack.
+const MESSAGE_LEN_MAX: usize = 1024;
+
+/// Message is the text of a `MESSAGE` formatted by `#[derive(JournalEntry)]`, held on the stack
+/// unless it's longer than 1024 bytes.
+#[derive(Clone, Debug)]
+pub struct Message {
+    buf: [u8; MESSAGE_LEN_MAX],
+    len: usize,
+    long: Option<Vec<u8>>,
+}
+
+impl Message {
+    pub fn new(args: std::fmt::Arguments<'_>) -> Self {
+        let mut message = Self {
+            buf: [0; MESSAGE_LEN_MAX],
+            len: 0,
+            long: None,
+        };
+        std::fmt::Write::write_fmt(&mut Spill(&mut message), args)
+            .expect("a Display implementation returned an error unexpectedly");
+        message
+    }
+}
+
+impl AsRef<[u8]> for Message {
+    fn as_ref(&self) -> &[u8] {
+        match &self.long {
+            Some(text) => text,
+            None => &self.buf[..self.len],
+        }
+    }
+}
+
+/// Spill writes into the buffer of a [[`Message`]], and once the text no longer fits, moves what's
+/// been written so far to the heap and continues there, so the arguments are formatted once.
+struct Spill<'a>(&'a mut Message);
+
+impl std::fmt::Write for Spill<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let message = &mut *self.0;
+        match &mut message.long {
+            Some(long) => long.extend_from_slice(s.as_bytes()),
+            None if s.len() <= MESSAGE_LEN_MAX - message.len => {
+                message.buf[message.len..][..s.len()].copy_from_slice(s.as_bytes());
+                message.len += s.len();
+            }
+            None => {
+                let mut long = message.buf[..message.len].to_vec();
+                long.extend_from_slice(s.as_bytes());
+                message.long = Some(long);
+            }
+        }
+        Ok(())
+    }
+}
+
+macro_rules! borrowed_field_value {
+    ($($ty:ty => |$value:ident| $bytes:expr),* $(,)?) => {
+        $(
+            impl FieldValue for $ty {
+                type Bytes<'a> = &'a [u8];
+
+                fn field_value(&self) -> Option<&[u8]> {
+                    let $value = self;
+                    Some($bytes)
+                }
+            }
+        )*
+    };
+}
+
+borrowed_field_value! {
+    str => |value| value.as_bytes(),
+    String => |value| value.as_bytes(),
+    [u8] => |value| value,
+    Vec<u8> => |value| value.as_slice(),
+    crate::raw::Priority => |value| value.as_str().as_bytes(),
+    crate::raw::Facility => |value| value.as_str().as_bytes(),
+    crate::id128::MessageId => |value| value.as_str().as_bytes(),
+}
+
+macro_rules! formatted_field_value {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl FieldValue for $ty {
+                type Bytes<'a> = Formatted;
+
+                fn field_value(&self) -> Option<Formatted> {
+                    Some(Formatted::new(self))
+                }
+            }
+        )*
+    };
+}
+
+formatted_field_value!(
+    bool,
+    char,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    crate::id128::Id128,
+);
+
+impl FieldValue for f32 {
+    type Bytes<'a> = Formatted<FLOAT_LEN_MAX>;
+
+    fn field_value(&self) -> Option<Formatted<FLOAT_LEN_MAX>> {
+        Some(Formatted::new(self))
+    }
+}
+
+impl FieldValue for f64 {
+    type Bytes<'a> = Formatted<FLOAT_LEN_MAX>;
+
+    fn field_value(&self) -> Option<Formatted<FLOAT_LEN_MAX>> {
+        Some(Formatted::new(self))
+    }
+}
+
+impl<T> FieldValue for Option<T>
+where
+    T: FieldValue,
+{
+    type Bytes<'a>
+        = T::Bytes<'a>
+    where
+        T: 'a;
+
+    fn field_value(&self) -> Option<T::Bytes<'_>> {
+        self.as_ref().and_then(T::field_value)
+    }
+}
+
+impl<T> FieldValue for &T
+where
+    T: FieldValue + ?Sized,
+{
+    type Bytes<'a>
+        = T::Bytes<'a>
+    where
+        Self: 'a;
+
+    fn field_value(&self) -> Option<T::Bytes<'_>> {
+        (**self).field_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAG: crate::raw::Field = crate::raw::Field::unchecked("TAG");
+
+    #[test]
+    fn test_tag_is_valid() {
+        assert!(crate::raw::Field::validate(TAG.as_str()).is_some());
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let mut entry = Entry::new()
+            .with(TAG, "first")
+            .with(crate::raw::MESSAGE, "Hello")
+            .with(TAG, b"second".as_slice());
+
+        assert_eq!(entry.len(), 3);
+        assert_eq!(entry.get(TAG), Some(b"first".as_slice()));
+        assert_eq!(
+            entry.get_all(TAG).collect::<Vec<_>>(),
+            [b"first".as_slice(), b"second"]
+        );
+        assert_eq!(
+            entry.fields().collect::<Vec<_>>(),
+            [TAG, crate::raw::MESSAGE]
+        );
+        assert_eq!(entry.get(crate::raw::PRIORITY), None);
+
+        assert_eq!(entry.remove(TAG), [b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(
+            entry.values().collect::<Vec<_>>(),
+            [(crate::raw::MESSAGE, b"Hello".as_slice())]
+        );
+    }
+
+    #[test]
+    fn test_field_value() {
+        fn bytes<T: FieldValue + ?Sized>(value: &T) -> Option<Vec<u8>> {
+            value.field_value().map(|value| value.as_ref().to_vec())
+        }
+
+        assert_eq!(bytes("text"), Some(b"text".to_vec()));
+        assert_eq!(bytes(&i128::MIN), Some(i128::MIN.to_string().into_bytes()));
+        assert_eq!(bytes(&true), Some(b"true".to_vec()));
+        assert_eq!(bytes(&0.5), Some(b"0.5".to_vec()));
+        for float in [
+            f64::MIN,
+            -f64::MIN_POSITIVE,
+            -5e-324,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ] {
+            assert_eq!(bytes(&float), Some(float.to_string().into_bytes()));
+        }
+        for float in [f32::MIN, -f32::MIN_POSITIVE, -1e-45, f32::NAN] {
+            assert_eq!(bytes(&float), Some(float.to_string().into_bytes()));
+        }
+        assert_eq!(bytes(&crate::raw::Priority::Info), Some(b"6".to_vec()));
+        assert_eq!(bytes(&Some(&12u8)), Some(b"12".to_vec()));
+        assert_eq!(bytes(&None::<u8>), None);
+    }
+
+    /// Counted displays how often it's been formatted.
+    struct Counted<'a>(&'a std::cell::Cell<usize>);
+
+    impl std::fmt::Display for Counted<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            self.0.set(self.0.get() + 1);
+            write!(f, "{}", self.0.get())
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let message = Message::new(format_args!("{} failed", "/index.html"));
+        assert!(message.long.is_none());
+        assert_eq!(message.as_ref(), b"/index.html failed");
+
+        let long = "a".repeat(MESSAGE_LEN_MAX);
+        let message = Message::new(format_args!("{}!", long));
+        assert!(message.long.is_some());
+        assert_eq!(message.as_ref(), format!("{}!", long).as_bytes());
+
+        // The text written before the buffer fills up is kept, and each argument formatted once.
+        let count = std::cell::Cell::new(0);
+        let counted = Counted(&count);
+        let message = Message::new(format_args!("{} {} {}", counted, long, counted));
+        assert_eq!(count.get(), 2);
+        assert_eq!(message.as_ref(), format!("1 {} 2", long).as_bytes());
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let entry: Entry = [(TAG, "a"), (TAG, "b")].into_iter().collect();
+        assert_eq!(entry.get_all(TAG).count(), 2);
+    }
+}
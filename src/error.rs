@@ -0,0 +1,267 @@
+//! Reporting of errors along with the chain of errors that caused them.
+//!
+//! An error is written to `ERROR`, and each of its sources to `ERROR_SOURCE_1`, `ERROR_SOURCE_2`
+//! and so on, so the chain can be read back without splitting a joined message.  When an error in
+//! the chain is an [[`std::io::Error`]] from the OS, its code is written to `ERRNO`, which
+//! `journalctl` shows as the error's description.
+//!
+//! The `log`, `slog` and `tracing` integrations write errors the same way, with the fields named
+//! after the key like any other, e.g. `cause` as `CAUSE` and `CAUSE_SOURCE_1`.  `ERRNO` is the
+//! exception: it's never prefixed or renamed, so `journalctl ERRNO=2` and the catalog find the
+//! entry, and when several errors are logged together only the first OS error code is written.
+//!
+//! ```no_run
+//! let journal = journaled::raw::JournalWriter::new().unwrap();
+//! if let Err(err) = std::fs::read("/etc/my-app.conf") {
+//!     journaled::error::send(&journal, &err).unwrap();
+//! }
+//! ```
+
+const ERROR: crate::raw::Field = crate::raw::Field::unchecked("ERROR");
+const SOURCE_INFIX: &str = "_SOURCE_";
+
+/// The error followed by each of its sources.
+pub fn chain<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> + Clone {
+    std::iter::successors(Some(error), |error| error.source())
+}
+
+/// The first OS error code in the chain, if there is one.
+pub fn errno(error: &(dyn std::error::Error + 'static)) -> Option<i32> {
+    chain(error).find_map(|error| {
+        error
+            .downcast_ref::<std::io::Error>()
+            .and_then(std::io::Error::raw_os_error)
+    })
+}
+
+/// The field that the `n`th source of an error written to `field` is written to, e.g.
+/// `ERROR_SOURCE_1`.
+///
+/// Names that would be too long have the end of `field` truncated.
+pub fn source_field(field: crate::raw::Field, n: usize) -> crate::raw::OwnedField {
+    let suffix = format!("{}{}", SOURCE_INFIX, n);
+    let len = field
+        .as_str()
+        .len()
+        .min(crate::raw::FIELD_LEN_MAX - suffix.len());
+    crate::raw::OwnedField::validate(format!("{}{}", &field.as_str()[..len], suffix))
+        .expect("source fields are valid")
+}
+
+/// The key that the `n`th source of an error logged with `key` is written to, e.g.
+/// `error_source_1`, for integrations that name fields after keys.
+pub fn source_key(key: &str, n: usize) -> String {
+    format!("{}{}{}", key, SOURCE_INFIX.to_ascii_lowercase(), n)
+}
+
+/// Part is one of the values that describe an error, as given by [[`parts`]].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Part {
+    /// The error itself.
+    Error,
+    /// The `n`th source of the error, counting from 1.
+    Source(usize),
+    /// The first OS error code in the chain.
+    Errno,
+}
+
+impl Part {
+    /// The field that this part of an error written to `field` is written to.
+    fn field(self, field: crate::raw::Field) -> crate::raw::OwnedField {
+        match self {
+            Part::Error => field.into(),
+            Part::Source(n) => source_field(field, n),
+            Part::Errno => crate::raw::ERRNO.into(),
+        }
+    }
+
+    /// The key that this part of an error logged with `key` is written to, which integrations
+    /// name like any other key, or `None` for the OS error code, which is written to `ERRNO` as is.
+    #[cfg(any(feature = "stdlog-kv", feature = "slog", feature = "tracing"))]
+    pub(crate) fn key(self, key: &str) -> Option<std::borrow::Cow<'_, str>> {
+        match self {
+            Part::Error => Some(key.into()),
+            Part::Source(n) => Some(source_key(key, n).into()),
+            Part::Errno => None,
+        }
+    }
+}
+
+/// The `Display` text of an error and of each of its sources, followed by the error's OS error
+/// code if it has one.
+pub(crate) fn parts<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = (Part, String)> + 'a {
+    chain(error)
+        .enumerate()
+        .map(|(n, error)| {
+            let part = match n {
+                0 => Part::Error,
+                n => Part::Source(n),
+            };
+            (part, error.to_string())
+        })
+        .chain(errno(error).map(|errno| (Part::Errno, errno.to_string())))
+}
+
+/// A filter that drops every `ERRNO` field after the first, so an entry describing several errors
+/// carries the code of the first OS error only.
+#[cfg(any(feature = "stdlog", feature = "slog", feature = "tracing"))]
+pub(crate) fn first_errno<V>() -> impl FnMut(&&(crate::raw::OwnedField, V)) -> bool + Clone {
+    let mut seen = false;
+    move |(field, _)| {
+        let errno = crate::raw::Field::from(field) == crate::raw::ERRNO;
+        let first = !(errno && seen);
+        seen |= errno;
+        first
+    }
+}
+
+/// ErrorFields are the fields that describe an error and its sources.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorFields {
+    message: String,
+    fields: Vec<(crate::raw::OwnedField, String)>,
+}
+
+impl ErrorFields {
+    /// Describes an error with the `ERROR` and `ERROR_SOURCE_*` fields.
+    pub fn new(error: &(dyn std::error::Error + 'static)) -> Self {
+        Self::with_field(error, ERROR)
+    }
+
+    /// Describes an error with fields named after `field`, e.g. `CAUSE` and `CAUSE_SOURCE_1`.
+    pub fn with_field(error: &(dyn std::error::Error + 'static), field: crate::raw::Field) -> Self {
+        Self {
+            message: error.to_string(),
+            fields: parts(error)
+                .map(|(part, value)| (part.field(field), value))
+                .collect(),
+        }
+    }
+
+    /// The `Display` text of the error, without its sources.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Is used to construct the tuples to be passed into [[`crate::raw::JournalWriter`]], along
+    /// with a `MESSAGE` of the caller's choosing.
+    pub fn as_values(&self) -> impl Iterator<Item = (crate::raw::Field<'_>, &str)> + Clone {
+        self.fields
+            .iter()
+            .map(|(field, value)| (field.into(), value.as_str()))
+    }
+}
+
+/// Sends an error at [[`crate::raw::Priority::Error`]], with its `Display` text as the `MESSAGE`.
+pub fn send(
+    writer: &crate::raw::JournalWriter,
+    error: &(dyn std::error::Error + 'static),
+) -> std::io::Result<()> {
+    let fields = ErrorFields::new(error);
+    writer.send(
+        [
+            crate::raw::Priority::Error.as_value(),
+            (crate::raw::MESSAGE, fields.message()),
+        ]
+        .into_iter()
+        .chain(fields.as_values()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Wrapped(std::io::Error);
+
+    impl std::fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "reading config")
+        }
+    }
+
+    impl std::error::Error for Wrapped {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    fn values(fields: &ErrorFields) -> Vec<(&str, &str)> {
+        fields
+            .as_values()
+            .map(|(field, value)| (field.as_str(), value))
+            .collect()
+    }
+
+    #[test]
+    fn test_error_fields() {
+        let error = Wrapped(std::io::Error::from_raw_os_error(2));
+        let fields = ErrorFields::new(&error);
+        let description = std::io::Error::from_raw_os_error(2).to_string();
+        assert_eq!(fields.message(), "reading config");
+        assert_eq!(
+            values(&fields),
+            [
+                ("ERROR", "reading config"),
+                ("ERROR_SOURCE_1", description.as_str()),
+                ("ERRNO", "2"),
+            ]
+        );
+
+        let error = std::io::Error::other("custom");
+        let cause = crate::raw::Field::validate("CAUSE").unwrap();
+        assert_eq!(
+            values(&ErrorFields::with_field(&error, cause)),
+            [("CAUSE", "custom")]
+        );
+    }
+
+    #[cfg(any(feature = "stdlog-kv", feature = "slog", feature = "tracing"))]
+    #[test]
+    fn test_parts() {
+        let error = Wrapped(std::io::Error::from_raw_os_error(2));
+        let keys: Vec<_> = parts(&error)
+            .map(|(part, _)| part.key("cause").map(|key| key.into_owned()))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                Some("cause".to_owned()),
+                Some("cause_source_1".to_owned()),
+                None
+            ]
+        );
+    }
+
+    #[cfg(any(feature = "stdlog", feature = "slog", feature = "tracing"))]
+    #[test]
+    fn test_first_errno() {
+        let field = |name| crate::raw::Field::validate(name).unwrap().into();
+        let fields: Vec<(crate::raw::OwnedField, &str)> = vec![
+            (field("ERROR"), "a"),
+            (crate::raw::ERRNO.into(), "2"),
+            (field("CAUSE"), "b"),
+            (crate::raw::ERRNO.into(), "13"),
+        ];
+        let kept: Vec<_> = fields
+            .iter()
+            .filter(first_errno())
+            .map(|(k, v)| (k.as_str(), *v))
+            .collect();
+        assert_eq!(kept, [("ERROR", "a"), ("ERRNO", "2"), ("CAUSE", "b")]);
+    }
+
+    #[test]
+    fn test_source_field() {
+        assert_eq!(source_field(ERROR, 12).as_str(), "ERROR_SOURCE_12");
+
+        let long = "A".repeat(64);
+        let field = source_field(crate::raw::Field::validate(&long).unwrap(), 1);
+        assert_eq!(field.as_str(), format!("{}_SOURCE_1", &long[..55]));
+    }
+}
@@ -0,0 +1,821 @@
+//! Serialization of entries into the Journal Export Format, as produced by `journalctl -o export`
+//! and consumed by `systemd-journal-remote`, and parsing of entries from it.
+//!
+//! Each entry is a series of fields followed by an empty line.  A field is written as
+//! `FIELD=value\n` when its value is printable UTF-8, and otherwise in the binary form of the
+//! native protocol: the field name, a new line, the length as a little-endian `u64`, the value,
+//! and a final new line.  The format is described at
+//! <https://systemd.io/JOURNAL_EXPORT_FORMATS/>.
+//!
+//! ```no_run
+//! let mut export = journaled::export::ExportWriter::new(std::io::stdout().lock());
+//! let address = journaled::export::Address::new(std::time::SystemTime::now());
+//!
+//! export
+//!     .write_entry(
+//!         &address,
+//!         [(journaled::raw::MESSAGE, "Hello World")].into_iter(),
+//!     )
+//!     .unwrap();
+//! ```
+
+const CURSOR: &str = "__CURSOR";
+const REALTIME_TIMESTAMP: &str = "__REALTIME_TIMESTAMP";
+const MONOTONIC_TIMESTAMP: &str = "__MONOTONIC_TIMESTAMP";
+const BOOT_ID: &str = "_BOOT_ID";
+
+/// Address is where an entry is located in a journal, as written ahead of its fields.
+///
+/// Only `__REALTIME_TIMESTAMP` is required.  `__CURSOR` is opaque and only meaningful to the
+/// journal it came from, so it's left out unless one is given.  `__MONOTONIC_TIMESTAMP` is
+/// relative to a boot, so it's always written along with `_BOOT_ID`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Address {
+    cursor: Option<String>,
+    realtime: std::time::SystemTime,
+    monotonic: Option<(crate::id128::Id128, std::time::Duration)>,
+}
+
+impl Address {
+    pub fn new(realtime: std::time::SystemTime) -> Self {
+        Self {
+            cursor: None,
+            realtime,
+            monotonic: None,
+        }
+    }
+
+    pub fn with_cursor<S>(mut self, cursor: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    /// Sets the time since `boot_id` started, e.g. as read from `CLOCK_MONOTONIC`.
+    pub fn with_monotonic(
+        mut self,
+        boot_id: crate::id128::Id128,
+        monotonic: std::time::Duration,
+    ) -> Self {
+        self.monotonic = Some((boot_id, monotonic));
+        self
+    }
+
+    pub fn cursor(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    pub fn realtime(&self) -> std::time::SystemTime {
+        self.realtime
+    }
+
+    pub fn monotonic(&self) -> Option<(crate::id128::Id128, std::time::Duration)> {
+        self.monotonic
+    }
+
+    /// The address fields in the order `journalctl` writes them.  Times are in microseconds, with
+    /// times before the epoch written as 0.
+    pub(crate) fn values(&self) -> impl Iterator<Item = (&'static str, String)> {
+        let realtime = self
+            .realtime
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let monotonic = self.monotonic.map(|(boot_id, monotonic)| {
+            [
+                (MONOTONIC_TIMESTAMP, monotonic.as_micros().to_string()),
+                (BOOT_ID, boot_id.to_string()),
+            ]
+        });
+
+        self.cursor
+            .clone()
+            .map(|cursor| (CURSOR, cursor))
+            .into_iter()
+            .chain(std::iter::once((
+                REALTIME_TIMESTAMP,
+                realtime.as_micros().to_string(),
+            )))
+            .chain(monotonic.into_iter().flatten())
+    }
+}
+
+/// Whether a value can be written as text, following `journalctl`: it must be UTF-8 without
+/// control characters other than tabs, and new lines if `newline` is set.
+pub(crate) fn is_printable(value: &[u8], newline: bool) -> bool {
+    match std::str::from_utf8(value) {
+        Ok(value) => !value
+            .chars()
+            .any(|c| c.is_control() && c != '\t' && !(newline && c == '\n')),
+        Err(_) => false,
+    }
+}
+
+/// Serializes a field in the text form if its value allows it, and the binary form otherwise.
+fn encode(data: &mut Vec<u8>, field: &str, value: &[u8]) {
+    if is_printable(value, false) {
+        data.extend(field.as_bytes());
+        data.push(b'=');
+        data.extend(value);
+        data.push(b'\n');
+    } else {
+        crate::raw::encode(data, &crate::raw::Field::unchecked(field), value);
+    }
+}
+
+/// ExportWriter writes entries in the export format to an underlying writer, such as a file or
+/// the input of `systemd-journal-remote`.
+///
+/// Each entry is written with a single call to the underlying writer, so wrapping it in a
+/// [[`std::io::BufWriter`]] is only needed to batch several entries together.
+#[derive(Debug)]
+pub struct ExportWriter<W> {
+    inner: W,
+}
+
+impl<W> ExportWriter<W>
+where
+    W: std::io::Write,
+{
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub fn write_entry<'a, I, V>(&mut self, address: &Address, values: I) -> std::io::Result<()>
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<str>,
+    {
+        self.write_entry_bytes(
+            address,
+            values.map(|(field, value)| (field, crate::raw::StrValue(value))),
+        )
+    }
+
+    /// Writes values that aren't necessarily valid UTF-8, in the binary form unless printable.
+    pub fn write_entry_bytes<'a, I, V>(
+        &mut self,
+        address: &Address,
+        values: I,
+    ) -> std::io::Result<()>
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<[u8]>,
+    {
+        let mut data = Vec::new();
+        for (field, value) in address.values() {
+            encode(&mut data, field, value.as_bytes());
+        }
+        for (field, value) in values {
+            encode(&mut data, field.as_str(), value.as_ref());
+        }
+        data.push(b'\n');
+
+        self.inner.write_all(&data)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// The largest value that's read, which matches the limit of `systemd-journal-remote`.
+pub(crate) const VALUE_LEN_MAX: u64 = 768 * 1024 * 1024;
+
+/// The longest line that's read: a name, `=`, a value, and a new line.
+const LINE_LEN_MAX: u64 = crate::raw::FIELD_LEN_MAX as u64 + VALUE_LEN_MAX + 2;
+
+/// The ways in which an export stream can fail to parse.
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// A field name was not valid, even allowing for the leading underscores of protected fields.
+    InvalidField,
+    /// An address field, such as `__REALTIME_TIMESTAMP`, did not have a valid value.
+    InvalidAddress,
+    /// A value, or the line holding a text field, was longer than the journal allows.
+    ValueTooLong,
+    /// A binary value was not followed by a new line.
+    MissingNewline,
+    /// The stream ended partway through a field.
+    UnexpectedEof,
+    /// Reading from the stream failed.
+    Io(std::io::Error),
+}
+
+/// Error returned when an export stream fails to parse, along with the byte offset into the
+/// stream where it occurred.
+#[derive(Debug)]
+pub struct ParseError {
+    offset: u64,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match &self.kind {
+            ParseErrorKind::InvalidField => "invalid field name",
+            ParseErrorKind::InvalidAddress => "invalid address field value",
+            ParseErrorKind::ValueTooLong => "value is too long",
+            ParseErrorKind::MissingNewline => "expected a new line after a binary value",
+            ParseErrorKind::UnexpectedEof => "unexpected end of stream",
+            ParseErrorKind::Io(_) => "failed to read",
+        };
+        write!(f, "byte {}: {}", self.offset, reason)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The ways in which a single field of an entry can be invalid, whichever format it's read from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum FieldError {
+    InvalidField,
+    InvalidAddress,
+}
+
+impl std::convert::From<FieldError> for ParseErrorKind {
+    fn from(err: FieldError) -> Self {
+        match err {
+            FieldError::InvalidField => ParseErrorKind::InvalidField,
+            FieldError::InvalidAddress => ParseErrorKind::InvalidAddress,
+        }
+    }
+}
+
+/// Whether a name is valid for a field read from a journal, which unlike the fields that
+/// clients send, may start with underscores.
+pub(crate) fn is_valid_name(name: &[u8]) -> bool {
+    match name.split_first() {
+        Some((first, _)) if first.is_ascii_digit() => false,
+        Some(_) => {
+            name.len() <= crate::raw::FIELD_LEN_MAX
+                && name
+                    .iter()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == &b'_')
+        }
+        None => false,
+    }
+}
+
+/// ExportEntry is an entry read from an export stream.
+///
+/// The fields that can be sent by clients are kept as an [[`crate::entry::Entry`]], including any
+/// that are repeated, so that the entry can be replayed with
+/// [[`crate::raw::JournalWriter::send_bytes`]].  Fields starting
+/// with underscores are added by journald, and are kept apart as protected fields.  The address
+/// fields are parsed into an [[`Address`]] when `__REALTIME_TIMESTAMP` is present.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ExportEntry {
+    address: Option<Address>,
+    protected: Vec<(String, Vec<u8>)>,
+    entry: crate::entry::Entry,
+    omitted: Vec<String>,
+}
+
+impl ExportEntry {
+    pub fn address(&self) -> Option<&Address> {
+        self.address.as_ref()
+    }
+
+    pub fn entry(&self) -> &crate::entry::Entry {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> crate::entry::Entry {
+        self.entry
+    }
+
+    /// The first value of a field.
+    pub fn get(&self, field: crate::raw::Field) -> Option<&[u8]> {
+        self.entry.get(field)
+    }
+
+    /// Every value of a field, in the order they were read.
+    pub fn get_all<'a>(
+        &'a self,
+        field: crate::raw::Field<'a>,
+    ) -> impl Iterator<Item = &'a [u8]> + Clone + 'a {
+        self.entry.get_all(field)
+    }
+
+    /// The fields added by journald, such as `_PID` and `__SEQNUM`, other than those that were
+    /// parsed into the address.  Repeated fields are listed once per value.
+    pub fn protected(&self) -> impl Iterator<Item = (&str, &[u8])> + Clone {
+        self.protected
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_slice()))
+    }
+
+    /// Is used to construct the tuples to be passed into [[`crate::raw::JournalWriter`]].
+    pub fn values(&self) -> impl Iterator<Item = (crate::raw::Field<'_>, &[u8])> + Clone {
+        self.entry.values()
+    }
+
+    /// The names of fields whose values were left out for being too large, which `journalctl`
+    /// does for the JSON formats.
+    pub fn omitted(&self) -> impl Iterator<Item = &str> + Clone {
+        self.omitted.iter().map(String::as_str)
+    }
+}
+
+/// EntryParser builds an [[`ExportEntry`]] from its fields as they're read.
+#[derive(Default)]
+pub(crate) struct EntryParser {
+    entry: ExportEntry,
+    address: AddressFields,
+    empty: bool,
+}
+
+impl EntryParser {
+    pub(crate) fn new() -> Self {
+        Self {
+            empty: true,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.empty
+    }
+
+    pub(crate) fn push(&mut self, name: Vec<u8>, value: Vec<u8>) -> Result<(), FieldError> {
+        let name = Self::validate(name)?;
+        if name.starts_with('_') {
+            if !self.address.parse(&name, &value)? {
+                self.entry.protected.push((name, value));
+            }
+        } else {
+            let field = crate::raw::OwnedField::validate(name).ok_or(FieldError::InvalidField)?;
+            self.entry.entry.push(field, value);
+        }
+        self.empty = false;
+        Ok(())
+    }
+
+    /// Records a field whose value was left out.
+    #[cfg(feature = "json")]
+    pub(crate) fn omit(&mut self, name: Vec<u8>) -> Result<(), FieldError> {
+        let name = Self::validate(name)?;
+        self.entry.omitted.push(name);
+        self.empty = false;
+        Ok(())
+    }
+
+    fn validate(name: Vec<u8>) -> Result<String, FieldError> {
+        if !is_valid_name(&name) {
+            return Err(FieldError::InvalidField);
+        }
+        Ok(String::from_utf8(name).expect("valid names are ascii"))
+    }
+
+    pub(crate) fn finish(self) -> ExportEntry {
+        let Self {
+            mut entry, address, ..
+        } = self;
+        if address.boot_id.is_none() {
+            if let Some(monotonic) = address.monotonic {
+                let value = monotonic.as_micros().to_string().into_bytes();
+                entry
+                    .protected
+                    .push((MONOTONIC_TIMESTAMP.to_owned(), value));
+            }
+        }
+        entry.address = address.finish();
+        entry
+    }
+
+    /// Finishes an entry whose address isn't stored in its fields, as in journal files.
+    #[cfg(feature = "file")]
+    pub(crate) fn finish_at(self, address: Address) -> ExportEntry {
+        let mut entry = self.entry;
+        entry.address = Some(address);
+        entry
+    }
+}
+
+/// The address fields of an entry as they're read, before they're known to be complete.
+#[derive(Default)]
+struct AddressFields {
+    cursor: Option<String>,
+    realtime: Option<std::time::SystemTime>,
+    monotonic: Option<std::time::Duration>,
+    boot_id: Option<crate::id128::Id128>,
+}
+
+impl AddressFields {
+    /// Records an address field, returning whether the name was one.
+    fn parse(&mut self, name: &str, value: &[u8]) -> Result<bool, FieldError> {
+        let text = || std::str::from_utf8(value).map_err(|_| FieldError::InvalidAddress);
+        let micros = || {
+            text()?
+                .parse()
+                .map(std::time::Duration::from_micros)
+                .map_err(|_| FieldError::InvalidAddress)
+        };
+        match name {
+            CURSOR => self.cursor = Some(text()?.to_owned()),
+            REALTIME_TIMESTAMP => self.realtime = Some(std::time::UNIX_EPOCH + micros()?),
+            MONOTONIC_TIMESTAMP => self.monotonic = Some(micros()?),
+            // _BOOT_ID is a protected field in its own right, so it's also kept as one.
+            BOOT_ID => {
+                self.boot_id =
+                    Some(crate::id128::Id128::parse(text()?).ok_or(FieldError::InvalidAddress)?);
+                return Ok(false);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn finish(self) -> Option<Address> {
+        let mut address = Address::new(self.realtime?);
+        address.cursor = self.cursor;
+        address.monotonic = self.boot_id.zip(self.monotonic);
+        Some(address)
+    }
+}
+
+/// ExportReader reads entries from an export stream, one at a time.
+///
+/// The stream is buffered internally, so files and pipes can be read from directly.  Once an error
+/// is returned, no further entries are read.
+///
+/// ```no_run
+/// let output = std::process::Command::new("journalctl")
+///     .args(["-o", "export", "-n", "10"])
+///     .output()
+///     .unwrap();
+/// for entry in journaled::export::ExportReader::new(output.stdout.as_slice()) {
+///     let entry = entry.unwrap();
+///     println!("{:?}", entry.get(journaled::raw::MESSAGE));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ExportReader<R> {
+    inner: std::io::BufReader<R>,
+    offset: u64,
+    line_len_max: u64,
+    failed: bool,
+}
+
+impl<R> ExportReader<R>
+where
+    R: std::io::Read,
+{
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner: std::io::BufReader::new(inner),
+            offset: 0,
+            line_len_max: LINE_LEN_MAX,
+            failed: false,
+        }
+    }
+
+    /// The number of bytes read from the stream so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the stream, discarding anything that was buffered but not yet parsed.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+
+    /// Reads the next entry, or `None` at the end of the stream.  Empty lines before an entry are
+    /// skipped, and the empty line after the last entry may be left out.
+    pub fn read_entry(&mut self) -> Result<Option<ExportEntry>, ParseError> {
+        use std::io::{BufRead, Read};
+
+        let mut parser = EntryParser::new();
+
+        loop {
+            let start = self.offset;
+            let mut line = Vec::new();
+            let n = (&mut self.inner)
+                .take(self.line_len_max)
+                .read_until(b'\n', &mut line)
+                .map_err(|err| self.error(ParseErrorKind::Io(err)))?;
+            self.offset += n as u64;
+
+            match line.pop() {
+                None => break,
+                Some(b'\n') => (),
+                Some(_) if n as u64 == self.line_len_max => {
+                    return Err(ParseError {
+                        offset: start,
+                        kind: ParseErrorKind::ValueTooLong,
+                    });
+                }
+                Some(_) => return Err(self.error(ParseErrorKind::UnexpectedEof)),
+            }
+            if line.is_empty() {
+                if parser.is_empty() {
+                    continue;
+                }
+                break;
+            }
+
+            let (name, value) = match line.iter().position(|c| *c == b'=') {
+                Some(i) => {
+                    let value = line.split_off(i + 1);
+                    line.truncate(i);
+                    (line, value)
+                }
+                None => {
+                    let value = self.read_binary()?;
+                    (line, value)
+                }
+            };
+            parser.push(name, value).map_err(|err| ParseError {
+                offset: start,
+                kind: err.into(),
+            })?;
+        }
+
+        if parser.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(parser.finish()))
+    }
+
+    /// Reads the length, value, and new line that follow the name of a binary field.
+    fn read_binary(&mut self) -> Result<Vec<u8>, ParseError> {
+        let len = self.read_exact(8)?;
+        let len = u64::from_le_bytes(len.try_into().expect("8 bytes were read"));
+        if len > VALUE_LEN_MAX {
+            return Err(ParseError {
+                offset: self.offset - 8,
+                kind: ParseErrorKind::ValueTooLong,
+            });
+        }
+
+        let value = self.read_exact(len)?;
+        if self.read_exact(1)? != b"\n" {
+            return Err(ParseError {
+                offset: self.offset - 1,
+                kind: ParseErrorKind::MissingNewline,
+            });
+        }
+        Ok(value)
+    }
+
+    /// Reads exactly `len` bytes, which are buffered as they arrive rather than allocated up
+    /// front, since the length comes from the stream.
+    fn read_exact(&mut self, len: u64) -> Result<Vec<u8>, ParseError> {
+        use std::io::Read;
+
+        let mut data = Vec::new();
+        let n = (&mut self.inner)
+            .take(len)
+            .read_to_end(&mut data)
+            .map_err(|err| self.error(ParseErrorKind::Io(err)))?;
+        self.offset += n as u64;
+        if (n as u64) < len {
+            return Err(self.error(ParseErrorKind::UnexpectedEof));
+        }
+        Ok(data)
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            offset: self.offset,
+            kind,
+        }
+    }
+}
+
+impl<R> Iterator for ExportReader<R>
+where
+    R: std::io::Read,
+{
+    type Item = Result<ExportEntry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.read_entry().transpose();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address() -> Address {
+        Address::new(
+            std::time::UNIX_EPOCH + std::time::Duration::from_micros(1_700_000_000_123_456),
+        )
+    }
+
+    #[test]
+    fn test_write_entry() {
+        let mut export = ExportWriter::new(Vec::new());
+        export
+            .write_entry(
+                &address(),
+                [
+                    (crate::raw::MESSAGE, "Hello\tWorld"),
+                    crate::raw::Priority::Info.as_value(),
+                ]
+                .into_iter(),
+            )
+            .unwrap();
+        export
+            .write_entry(&address(), [(crate::raw::MESSAGE, "Again")].into_iter())
+            .unwrap();
+
+        assert_eq!(
+            export.into_inner(),
+            b"__REALTIME_TIMESTAMP=1700000000123456\n\
+              MESSAGE=Hello\tWorld\n\
+              PRIORITY=6\n\
+              \n\
+              __REALTIME_TIMESTAMP=1700000000123456\n\
+              MESSAGE=Again\n\
+              \n"
+        );
+    }
+
+    #[test]
+    fn test_write_binary() {
+        let mut export = ExportWriter::new(Vec::new());
+        export
+            .write_entry_bytes(
+                &address(),
+                [
+                    (crate::raw::MESSAGE, b"two\nlines".as_slice()),
+                    (crate::raw::MESSAGE_ID, b"\xff"),
+                ]
+                .into_iter(),
+            )
+            .unwrap();
+
+        let mut expected = b"__REALTIME_TIMESTAMP=1700000000123456\nMESSAGE\n".to_vec();
+        expected.extend(9u64.to_le_bytes());
+        expected.extend(b"two\nlines\nMESSAGE_ID\n");
+        expected.extend(1u64.to_le_bytes());
+        expected.extend(b"\xff\n\n");
+        assert_eq!(export.into_inner(), expected);
+    }
+
+    #[test]
+    fn test_address() {
+        let boot_id = crate::id128::Id128::from_u128(0x6bb4d5e81ea14a3b9a4e77d8f1b3bf0e);
+        let address = address()
+            .with_cursor("s=abc;i=1")
+            .with_monotonic(boot_id, std::time::Duration::from_micros(42));
+
+        let mut export = ExportWriter::new(Vec::new());
+        export
+            .write_entry(&address, std::iter::empty::<(crate::raw::Field, &str)>())
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(export.get_ref()).unwrap(),
+            "__CURSOR=s=abc;i=1\n\
+             __REALTIME_TIMESTAMP=1700000000123456\n\
+             __MONOTONIC_TIMESTAMP=42\n\
+             _BOOT_ID=6bb4d5e81ea14a3b9a4e77d8f1b3bf0e\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn test_read_round_trip() {
+        let boot_id = crate::id128::Id128::from_u128(0x6bb4d5e81ea14a3b9a4e77d8f1b3bf0e);
+        let address = address()
+            .with_cursor("s=abc;i=1")
+            .with_monotonic(boot_id, std::time::Duration::from_micros(42));
+        let mut export = ExportWriter::new(Vec::new());
+        export
+            .write_entry_bytes(
+                &address,
+                [
+                    (crate::raw::MESSAGE, b"two\nlines".as_slice()),
+                    (crate::raw::PRIORITY, b"6"),
+                ]
+                .into_iter(),
+            )
+            .unwrap();
+        let tag = crate::raw::Field::validate("TAG").unwrap();
+        export
+            .write_entry(
+                &address,
+                [(crate::raw::MESSAGE, "Again"), (tag, "a"), (tag, "b")].into_iter(),
+            )
+            .unwrap();
+
+        let entries = ExportReader::new(export.get_ref().as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].address(), Some(&address));
+        assert_eq!(
+            entries[0].values().collect::<Vec<_>>(),
+            [
+                (crate::raw::MESSAGE, b"two\nlines".as_slice()),
+                (crate::raw::PRIORITY, b"6")
+            ]
+        );
+        assert_eq!(
+            entries[0].protected().collect::<Vec<_>>(),
+            [("_BOOT_ID", b"6bb4d5e81ea14a3b9a4e77d8f1b3bf0e".as_slice())]
+        );
+        assert_eq!(
+            entries[1].get(crate::raw::MESSAGE),
+            Some(b"Again".as_slice())
+        );
+        assert_eq!(
+            entries[1].get_all(tag).collect::<Vec<_>>(),
+            [b"a".as_slice(), b"b"]
+        );
+    }
+
+    #[test]
+    fn test_read_protected() {
+        let text = "\n__SEQNUM=12\n_PID=1\n__MONOTONIC_TIMESTAMP=42\nMESSAGE=a=b\n";
+        let mut reader = ExportReader::new(text.as_bytes());
+        let entry = reader.read_entry().unwrap().unwrap();
+
+        assert_eq!(entry.address(), None);
+        assert_eq!(
+            entry.protected().collect::<Vec<_>>(),
+            [
+                ("__SEQNUM", b"12".as_slice()),
+                ("_PID", b"1"),
+                ("__MONOTONIC_TIMESTAMP", b"42"),
+            ]
+        );
+        assert_eq!(entry.get(crate::raw::MESSAGE), Some(b"a=b".as_slice()));
+        assert!(reader.read_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |data: &[u8]| {
+            let mut reader = ExportReader::new(data);
+            let error = reader.next().unwrap().unwrap_err();
+            assert!(reader.next().is_none());
+            error
+        };
+
+        let err = error(b"MESSAGE=a\nlower=b\n");
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidField));
+        assert_eq!(err.offset(), 10);
+        assert_eq!(err.to_string(), "byte 10: invalid field name");
+
+        let err = error(b"MESSAGE=a\n__REALTIME_TIMESTAMP=now\n");
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidAddress));
+        assert_eq!(err.offset(), 10);
+
+        let err = error(b"MESSAGE=a");
+        assert!(matches!(err.kind(), ParseErrorKind::UnexpectedEof));
+        assert_eq!(err.offset(), 9);
+
+        let err = error(b"MESSAGE\n\x05\0\0");
+        assert!(matches!(err.kind(), ParseErrorKind::UnexpectedEof));
+        assert_eq!(err.offset(), 11);
+
+        let err = error(b"MESSAGE\n\x01\0\0\0\0\0\0\0ab");
+        assert!(matches!(err.kind(), ParseErrorKind::MissingNewline));
+        assert_eq!(err.offset(), 17);
+
+        let err = error(b"MESSAGE\n\xff\xff\xff\xff\xff\xff\xff\xff");
+        assert!(matches!(err.kind(), ParseErrorKind::ValueTooLong));
+        assert_eq!(err.offset(), 8);
+
+        let mut reader = ExportReader::new(b"MESSAGE=a\nMESSAGE=abcdef".as_slice());
+        reader.line_len_max = 12;
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), ParseErrorKind::ValueTooLong));
+        assert_eq!(err.offset(), 10);
+    }
+}
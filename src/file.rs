@@ -0,0 +1,1470 @@
+//! Reading of journal files, the binary format that journald stores entries in, without going
+//! through libsystemd.
+//!
+//! A file starts with a header, which is followed by an arena of objects, each aligned to 8 bytes.
+//! Entries are found in order through a chain of entry arrays.  Each entry refers to the data
+//! objects that hold its `FIELD=value` pairs, which are shared between entries, and the distinct
+//! fields and data objects are linked from hash tables.  The format is described at
+//! <https://systemd.io/JOURNAL_FILE_FORMAT/>.
+//!
+//! Data objects above a size threshold may be compressed with XZ, LZ4 or ZSTD, which are
+//! decompressed when the `xz`, `lz4` and `zstd` features are enabled.  Files that use a codec
+//! whose feature isn't enabled fail to open with [[`ParseErrorKind::UnsupportedFlags`]].
+//! Compact files, with 32-bit offsets, and files whose hash tables are keyed are always read.
+//!
+//! Each file is read on its own, so entries from the files of a journal aren't interleaved.
+//!
+//! ```no_run
+//! for path in journaled::file::find_files(journaled::file::SYSTEM_DIRECTORY).unwrap() {
+//!     let reader = journaled::file::JournalReader::open(&path).unwrap();
+//!     for entry in reader {
+//!         let entry = entry.unwrap();
+//!         println!("{:?}", entry.get(journaled::raw::MESSAGE));
+//!     }
+//! }
+//! ```
+
+/// Where journald stores entries when the journal is persistent.
+pub const SYSTEM_DIRECTORY: &str = "/var/log/journal";
+/// Where journald stores entries when the journal is volatile, and before `/var` is available.
+pub const RUNTIME_DIRECTORY: &str = "/run/log/journal";
+
+const SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+/// The size of the header up to `tail_entry_monotonic`, which every version of the format has.
+const HEADER_SIZE_MIN: u64 = 208;
+const OBJECT_HEADER_SIZE: u64 = 16;
+
+const INCOMPATIBLE_COMPRESSED_XZ: u32 = 1 << 0;
+const INCOMPATIBLE_COMPRESSED_LZ4: u32 = 1 << 1;
+const INCOMPATIBLE_KEYED_HASH: u32 = 1 << 2;
+const INCOMPATIBLE_COMPRESSED_ZSTD: u32 = 1 << 3;
+const INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+/// The incompatible flags that can be read, which depends on the codecs that are enabled.
+const INCOMPATIBLE_SUPPORTED: u32 = INCOMPATIBLE_KEYED_HASH
+    | INCOMPATIBLE_COMPACT
+    | if cfg!(feature = "xz") {
+        INCOMPATIBLE_COMPRESSED_XZ
+    } else {
+        0
+    }
+    | if cfg!(feature = "lz4") {
+        INCOMPATIBLE_COMPRESSED_LZ4
+    } else {
+        0
+    }
+    | if cfg!(feature = "zstd") {
+        INCOMPATIBLE_COMPRESSED_ZSTD
+    } else {
+        0
+    };
+
+const OBJECT_COMPRESSED_XZ: u8 = 1 << 0;
+const OBJECT_COMPRESSED_LZ4: u8 = 1 << 1;
+const OBJECT_COMPRESSED_ZSTD: u8 = 1 << 2;
+
+/// The most that an LZ4 block can expand by when it's decompressed.
+#[cfg(feature = "lz4")]
+const LZ4_RATIO_MAX: u64 = 255;
+
+/// Every journal file found below `directory`, sorted by path.  journald keeps the files of each
+/// machine in a directory named after its id, and archived files alongside the active ones.
+pub fn find_files<P>(directory: P) -> std::io::Result<Vec<std::path::PathBuf>>
+where
+    P: AsRef<std::path::Path>,
+{
+    let mut files = Vec::new();
+    let mut directories = vec![directory.as_ref().to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                directories.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "journal")
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The ways in which a journal file can fail to be read.
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// The file doesn't start with a journal file header, or the header doesn't fit the file.
+    InvalidHeader,
+    /// The file uses features that can't be read, given as its unsupported incompatible flags.
+    UnsupportedFlags(u32),
+    /// An object had an unexpected type, or an offset or size that doesn't fit the file.
+    InvalidObject,
+    /// A data object didn't hold a valid `FIELD=value` pair.
+    InvalidField,
+    /// A compressed data object failed to decompress, or was larger than the journal allows.
+    InvalidCompression,
+    /// The file ended partway through an object.
+    UnexpectedEof,
+    /// Reading from the file failed.
+    Io(std::io::Error),
+}
+
+/// Error returned when a journal file fails to be read, along with the byte offset into the file
+/// of the object or header that couldn't be read.
+#[derive(Debug)]
+pub struct ParseError {
+    offset: u64,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ParseErrorKind::InvalidHeader => write!(f, "byte {}: invalid header", self.offset),
+            ParseErrorKind::UnsupportedFlags(flags) => write!(
+                f,
+                "byte {}: unsupported incompatible flags {:#x}",
+                self.offset, flags
+            ),
+            ParseErrorKind::InvalidObject => write!(f, "byte {}: invalid object", self.offset),
+            ParseErrorKind::InvalidField => write!(f, "byte {}: invalid field", self.offset),
+            ParseErrorKind::InvalidCompression => {
+                write!(f, "byte {}: invalid compressed data", self.offset)
+            }
+            ParseErrorKind::UnexpectedEof => {
+                write!(f, "byte {}: unexpected end of file", self.offset)
+            }
+            ParseErrorKind::Io(_) => write!(f, "byte {}: failed to read", self.offset),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::convert::From<crate::export::FieldError> for ParseErrorKind {
+    fn from(_: crate::export::FieldError) -> Self {
+        ParseErrorKind::InvalidField
+    }
+}
+
+/// State is whether a file is still being written to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum State {
+    /// The file was closed cleanly, and may be written to again.
+    Offline,
+    /// The file is open for writing, or wasn't closed cleanly.
+    Online,
+    /// The file was rotated, and won't be written to again.
+    Archived,
+}
+
+/// Header is the start of a journal file, which describes the file as a whole.
+///
+/// The counts and timestamps are as of when the header was read, since journald keeps appending
+/// to online files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Header {
+    incompatible_flags: u32,
+    state: State,
+    file_id: crate::id128::Id128,
+    machine_id: crate::id128::Id128,
+    boot_id: crate::id128::Id128,
+    seqnum_id: crate::id128::Id128,
+    header_size: u64,
+    arena_size: u64,
+    data_hash_table_offset: u64,
+    data_hash_table_size: u64,
+    field_hash_table_offset: u64,
+    field_hash_table_size: u64,
+    n_objects: u64,
+    n_entries: u64,
+    tail_seqnum: u64,
+    head_seqnum: u64,
+    entry_array_offset: u64,
+    head_realtime: u64,
+    tail_realtime: u64,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, ParseErrorKind> {
+        if !data.starts_with(SIGNATURE) {
+            return Err(ParseErrorKind::InvalidHeader);
+        }
+        let incompatible_flags = le32(data, 12);
+        if incompatible_flags & !INCOMPATIBLE_SUPPORTED != 0 {
+            return Err(ParseErrorKind::UnsupportedFlags(
+                incompatible_flags & !INCOMPATIBLE_SUPPORTED,
+            ));
+        }
+        let state = match data[16] {
+            0 => State::Offline,
+            1 => State::Online,
+            2 => State::Archived,
+            _ => return Err(ParseErrorKind::InvalidHeader),
+        };
+
+        let header = Self {
+            incompatible_flags,
+            state,
+            file_id: id128(data, 24),
+            machine_id: id128(data, 40),
+            boot_id: id128(data, 56),
+            seqnum_id: id128(data, 72),
+            header_size: le64(data, 88),
+            arena_size: le64(data, 96),
+            data_hash_table_offset: le64(data, 104),
+            data_hash_table_size: le64(data, 112),
+            field_hash_table_offset: le64(data, 120),
+            field_hash_table_size: le64(data, 128),
+            n_objects: le64(data, 144),
+            n_entries: le64(data, 152),
+            tail_seqnum: le64(data, 160),
+            head_seqnum: le64(data, 168),
+            entry_array_offset: le64(data, 176),
+            head_realtime: le64(data, 184),
+            tail_realtime: le64(data, 192),
+        };
+        if header.header_size < HEADER_SIZE_MIN || header.header_size % 8 != 0 {
+            return Err(ParseErrorKind::InvalidHeader);
+        }
+        Ok(header)
+    }
+
+    /// The offset just past the last object.
+    fn arena_end(&self) -> Option<u64> {
+        self.header_size.checked_add(self.arena_size)
+    }
+
+    /// Whether offsets within the file are stored in 32 bits, which newer versions of systemd do
+    /// for files of up to 4 GiB.
+    pub fn is_compact(&self) -> bool {
+        self.incompatible_flags & INCOMPATIBLE_COMPACT != 0
+    }
+
+    /// Whether the hash tables are keyed by the file id, which newer versions of systemd do so
+    /// that the chains can't be made long on purpose.
+    pub fn has_keyed_hash(&self) -> bool {
+        self.incompatible_flags & INCOMPATIBLE_KEYED_HASH != 0
+    }
+
+    /// The hash that fields and data objects are looked up by.
+    fn hash(&self, data: &[u8]) -> u64 {
+        if self.has_keyed_hash() {
+            siphash24(self.file_id.as_bytes(), data)
+        } else {
+            jenkins_hash64(data)
+        }
+    }
+
+    /// Reads an offset to another object, as stored in the items of entries and entry arrays.
+    fn item(&self, item: &[u8]) -> u64 {
+        if self.is_compact() {
+            u64::from(le32(item, 0))
+        } else {
+            le64(item, 0)
+        }
+    }
+
+    /// The size of each item of an entry, which holds the hash of the data besides its offset
+    /// unless the file is compact.
+    fn entry_item_size(&self) -> usize {
+        if self.is_compact() {
+            4
+        } else {
+            16
+        }
+    }
+
+    fn entry_array_item_size(&self) -> usize {
+        if self.is_compact() {
+            4
+        } else {
+            8
+        }
+    }
+
+    /// Where the payload of a data object starts, after the tail of its entry array in compact
+    /// files.
+    fn data_payload_offset(&self) -> usize {
+        if self.is_compact() {
+            72
+        } else {
+            64
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn file_id(&self) -> crate::id128::Id128 {
+        self.file_id
+    }
+
+    pub fn machine_id(&self) -> crate::id128::Id128 {
+        self.machine_id
+    }
+
+    /// The boot of the last entry that was written.
+    pub fn boot_id(&self) -> crate::id128::Id128 {
+        self.boot_id
+    }
+
+    /// Identifies the sequence that the sequence numbers of entries belong to, which is shared
+    /// between the files that journald rotates through.
+    pub fn seqnum_id(&self) -> crate::id128::Id128 {
+        self.seqnum_id
+    }
+
+    pub fn n_entries(&self) -> u64 {
+        self.n_entries
+    }
+
+    pub fn head_seqnum(&self) -> u64 {
+        self.head_seqnum
+    }
+
+    pub fn tail_seqnum(&self) -> u64 {
+        self.tail_seqnum
+    }
+
+    pub fn head_realtime(&self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_micros(self.head_realtime)
+    }
+
+    pub fn tail_realtime(&self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_micros(self.tail_realtime)
+    }
+}
+
+/// The types of objects that are read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ObjectType {
+    Data = 1,
+    Field = 2,
+    Entry = 3,
+    FieldHashTable = 5,
+    EntryArray = 6,
+}
+
+impl ObjectType {
+    /// The smallest an object of this type can be, including the object header.
+    fn min_size(self) -> u64 {
+        match self {
+            ObjectType::Data => 64,
+            ObjectType::Field => 40,
+            ObjectType::Entry => 64,
+            ObjectType::FieldHashTable => 16,
+            ObjectType::EntryArray => 24,
+        }
+    }
+}
+
+/// Where the reader is in the chain of entry arrays.
+#[derive(Debug, Default)]
+struct Position {
+    next_array: u64,
+    items: std::vec::IntoIter<u64>,
+    read: u64,
+}
+
+/// JournalReader reads the entries of a journal file, one at a time, in the order they were
+/// written.
+///
+/// Entries are read as [[`crate::export::ExportEntry`]], the same as from an export stream, with
+/// the address taken from the entry and the fields from its data objects.  The number of entries
+/// is fixed when the file is opened, so entries that journald appends afterwards aren't read.
+/// Once an error is returned, no further entries are read.
+#[derive(Debug)]
+pub struct JournalReader<R> {
+    inner: R,
+    header: Header,
+    position: Position,
+    failed: bool,
+}
+
+impl JournalReader<std::fs::File> {
+    pub fn open<P>(path: P) -> Result<Self, ParseError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let file = std::fs::File::open(path).map_err(|err| ParseError {
+            offset: 0,
+            kind: ParseErrorKind::Io(err),
+        })?;
+        Self::new(file)
+    }
+}
+
+impl<R> JournalReader<R>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    /// Reads the header, and checks that the file can be read.
+    pub fn new(mut inner: R) -> Result<Self, ParseError> {
+        let error = |kind| ParseError { offset: 0, kind };
+        let len = inner
+            .seek(std::io::SeekFrom::End(0))
+            .map_err(|err| error(ParseErrorKind::Io(err)))?;
+
+        let data = read_at(&mut inner, 0, HEADER_SIZE_MIN)?;
+        let header = Header::parse(&data).map_err(error)?;
+        if header.arena_end().map_or(true, |end| end > len) {
+            return Err(error(ParseErrorKind::InvalidHeader));
+        }
+        let position = Position {
+            next_array: header.entry_array_offset,
+            ..Default::default()
+        };
+        Ok(Self {
+            inner,
+            header,
+            position,
+            failed: false,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Limits the entries that are read to those that match, and restarts reading from the first
+    /// of them.  The matches are resolved when set, through the data hash table and the entry
+    /// arrays of each data object, so entries that don't match are never read.  Setting empty
+    /// matches reads every entry again.
+    pub fn set_matches(&mut self, matches: &crate::matches::Matches) -> Result<(), ParseError> {
+        let entries = matches.resolve(|field, value| {
+            let payload = [field.as_bytes(), b"=", value].concat();
+            match self.find_data(&payload)? {
+                Some(object) => self.data_entries(&object),
+                None => Ok(Vec::new()),
+            }
+        })?;
+        self.position = match entries {
+            Some(entries) => Position {
+                items: entries.into_iter(),
+                ..Default::default()
+            },
+            None => Position {
+                next_array: self.header.entry_array_offset,
+                ..Default::default()
+            },
+        };
+        self.failed = false;
+        Ok(())
+    }
+
+    /// Reads the next entry, or `None` once every entry has been read.
+    pub fn read_entry(&mut self) -> Result<Option<crate::export::ExportEntry>, ParseError> {
+        match self.next_entry_offset()? {
+            Some(offset) => self.read_entry_at(offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The names of every field in the file, sorted, as listed by `journalctl --fields`.
+    pub fn fields(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut fields = Vec::new();
+        for head in self.read_field_hash_table()? {
+            let mut offset = head;
+            while offset != 0 {
+                let object = self.read_object(offset, ObjectType::Field)?;
+                let name = String::from_utf8(object.data[40..].to_vec())
+                    .map_err(|_| object.error(ParseErrorKind::InvalidField))?;
+                fields.push(name);
+                offset = self.next_in_chain(&object, 24, fields.len())?;
+            }
+        }
+        fields.sort();
+        Ok(fields)
+    }
+
+    /// Every distinct value of a field in the file, in no particular order, as listed by
+    /// `journalctl --field`.
+    pub fn unique_values(&mut self, field: &str) -> Result<Vec<Vec<u8>>, ParseError> {
+        let mut values = Vec::new();
+        let mut offset = match self.find_field(field)? {
+            Some(object) => le64(&object.data, 32),
+            None => return Ok(values),
+        };
+        while offset != 0 {
+            let (object, payload) = self.read_data(offset)?;
+            let (name, value) = object.field(&payload)?;
+            if name != field.as_bytes() {
+                return Err(object.error(ParseErrorKind::InvalidObject));
+            }
+            values.push(value.to_vec());
+            offset = self.next_in_chain(&object, 32, values.len())?;
+        }
+        Ok(values)
+    }
+
+    /// Looks up the field object for a name in the field hash table.
+    fn find_field(&mut self, field: &str) -> Result<Option<Object>, ParseError> {
+        let hash = self.header.hash(field.as_bytes());
+        let (table, size) = (
+            self.header.field_hash_table_offset,
+            self.header.field_hash_table_size,
+        );
+        let mut offset = self.hash_table_head(table, size, hash)?;
+        let mut n = 0;
+        while offset != 0 {
+            let object = self.read_object(offset, ObjectType::Field)?;
+            if le64(&object.data, 16) == hash && &object.data[40..] == field.as_bytes() {
+                return Ok(Some(object));
+            }
+            n += 1;
+            offset = self.next_in_chain(&object, 24, n)?;
+        }
+        Ok(None)
+    }
+
+    /// Looks up the data object for a `FIELD=value` payload in the data hash table.
+    fn find_data(&mut self, payload: &[u8]) -> Result<Option<Object>, ParseError> {
+        let hash = self.header.hash(payload);
+        let (table, size) = (
+            self.header.data_hash_table_offset,
+            self.header.data_hash_table_size,
+        );
+        let mut offset = self.hash_table_head(table, size, hash)?;
+        let mut n = 0;
+        while offset != 0 {
+            let (object, data) = self.read_data(offset)?;
+            if le64(&object.data, 16) == hash && data == payload {
+                return Ok(Some(object));
+            }
+            n += 1;
+            offset = self.next_in_chain(&object, 24, n)?;
+        }
+        Ok(None)
+    }
+
+    /// The offsets of the entries that refer to a data object, in order.  The first is held by
+    /// the data object itself, and the rest by its own chain of entry arrays.
+    fn data_entries(&mut self, data: &Object) -> Result<Vec<u64>, ParseError> {
+        let mut entries = Vec::new();
+        let first = le64(&data.data, 40);
+        let mut next_array = le64(&data.data, 48);
+        let n_entries = le64(&data.data, 56);
+        if first == 0 {
+            return Ok(entries);
+        }
+        entries.push(first);
+        while (entries.len() as u64) < n_entries && next_array != 0 {
+            let (next, items) = self.read_entry_array(next_array)?;
+            let remaining = (n_entries - entries.len() as u64) as usize;
+            entries.extend(
+                items
+                    .into_iter()
+                    .take_while(|offset| *offset != 0)
+                    .take(remaining),
+            );
+            next_array = next;
+        }
+        Ok(entries)
+    }
+
+    /// The head of the chain in a hash table for a hash, reading only its bucket.
+    fn hash_table_head(&mut self, table: u64, size: u64, hash: u64) -> Result<u64, ParseError> {
+        let end = self.header.arena_end().expect("checked when opened");
+        if table < self.header.header_size + OBJECT_HEADER_SIZE
+            || table.saturating_add(size) > end
+            || size % 16 != 0
+        {
+            return Err(ParseError {
+                offset: 0,
+                kind: ParseErrorKind::InvalidHeader,
+            });
+        }
+        if size == 0 {
+            return Ok(0);
+        }
+        let bucket = table + 16 * (hash % (size / 16));
+        Ok(le64(&read_at(&mut self.inner, bucket, 8)?, 0))
+    }
+
+    /// The head of each chain in the field hash table.
+    fn read_field_hash_table(&mut self) -> Result<Vec<u64>, ParseError> {
+        let offset = self
+            .header
+            .field_hash_table_offset
+            .checked_sub(OBJECT_HEADER_SIZE)
+            .ok_or(ParseError {
+                offset: 0,
+                kind: ParseErrorKind::InvalidHeader,
+            })?;
+        let object = self.read_object(offset, ObjectType::FieldHashTable)?;
+        let items = &object.data[OBJECT_HEADER_SIZE as usize..];
+        if items.len() as u64 != self.header.field_hash_table_size || items.len() % 16 != 0 {
+            return Err(object.error(ParseErrorKind::InvalidObject));
+        }
+        Ok(items.chunks_exact(16).map(|item| le64(item, 0)).collect())
+    }
+
+    /// The next object in a chain, linked from `at` within `object`.  Since every object appears
+    /// in a chain at most once, a chain longer than the number of objects must loop.
+    fn next_in_chain(&self, object: &Object, at: usize, n: usize) -> Result<u64, ParseError> {
+        if n as u64 >= self.header.n_objects {
+            return Err(object.error(ParseErrorKind::InvalidObject));
+        }
+        Ok(le64(&object.data, at))
+    }
+
+    /// The offset of the next entry in the chain of entry arrays.
+    fn next_entry_offset(&mut self) -> Result<Option<u64>, ParseError> {
+        while self.position.read < self.header.n_entries {
+            if let Some(offset) = self.position.items.next() {
+                // Arrays are filled in order, so the first unused item is the end of the entries.
+                if offset == 0 {
+                    break;
+                }
+                self.position.read += 1;
+                return Ok(Some(offset));
+            }
+
+            let offset = self.position.next_array;
+            if offset == 0 {
+                break;
+            }
+            let (next_array, items) = self.read_entry_array(offset)?;
+            self.position.next_array = next_array;
+            self.position.items = items.into_iter();
+        }
+        Ok(None)
+    }
+
+    /// Reads an entry array, returning the offset of the next array in the chain and the items,
+    /// including any unused items at the end.
+    fn read_entry_array(&mut self, offset: u64) -> Result<(u64, Vec<u64>), ParseError> {
+        let object = self.read_object(offset, ObjectType::EntryArray)?;
+        let items = &object.data[24..];
+        let item_size = self.header.entry_array_item_size();
+        let next_array = le64(&object.data, 16);
+        // Arrays are only ever appended, which also rules out loops.
+        if items.len() % item_size != 0 || (next_array != 0 && next_array <= offset) {
+            return Err(object.error(ParseErrorKind::InvalidObject));
+        }
+        let items = items
+            .chunks_exact(item_size)
+            .map(|item| self.header.item(item))
+            .collect();
+        Ok((next_array, items))
+    }
+
+    fn read_entry_at(&mut self, offset: u64) -> Result<crate::export::ExportEntry, ParseError> {
+        let object = self.read_object(offset, ObjectType::Entry)?;
+        let seqnum = le64(&object.data, 16);
+        let realtime = le64(&object.data, 24);
+        let monotonic = le64(&object.data, 32);
+        let boot_id = id128(&object.data, 40);
+        let xor_hash = le64(&object.data, 56);
+        let items = &object.data[64..];
+        let item_size = self.header.entry_item_size();
+        if items.len() % item_size != 0 {
+            return Err(object.error(ParseErrorKind::InvalidObject));
+        }
+
+        // The same form as sd_journal_get_cursor, so that journalctl accepts it.
+        let cursor = format!(
+            "s={};i={:x};b={};m={:x};t={:x};x={:x}",
+            self.header.seqnum_id, seqnum, boot_id, monotonic, realtime, xor_hash
+        );
+        let address = crate::export::Address::new(std::time::UNIX_EPOCH + micros(realtime))
+            .with_cursor(cursor)
+            .with_monotonic(boot_id, micros(monotonic));
+
+        let mut parser = crate::export::EntryParser::new();
+        for item in items.chunks_exact(item_size) {
+            let (data, payload) = self.read_data(self.header.item(item))?;
+            let (name, value) = data.field(&payload)?;
+            parser
+                .push(name.to_vec(), value.to_vec())
+                .map_err(|err| data.error(err.into()))?;
+        }
+        Ok(parser.finish_at(address))
+    }
+
+    /// Reads a data object along with its payload, which is decompressed if need be.
+    fn read_data(&mut self, offset: u64) -> Result<(Object, Vec<u8>), ParseError> {
+        let object = self.read_object(offset, ObjectType::Data)?;
+        let payload = object
+            .data
+            .get(self.header.data_payload_offset()..)
+            .ok_or_else(|| object.error(ParseErrorKind::InvalidObject))?;
+        let payload = decompress(object.data[1], payload)
+            .ok_or_else(|| object.error(ParseErrorKind::InvalidCompression))?;
+        Ok((object, payload))
+    }
+
+    /// Reads a whole object, checking that it has the expected type and fits in the arena.
+    fn read_object(&mut self, offset: u64, kind: ObjectType) -> Result<Object, ParseError> {
+        let invalid = ParseError {
+            offset,
+            kind: ParseErrorKind::InvalidObject,
+        };
+        let end = self.header.arena_end().expect("checked when opened");
+        if offset % 8 != 0
+            || offset < self.header.header_size
+            || offset.saturating_add(OBJECT_HEADER_SIZE) > end
+        {
+            return Err(invalid);
+        }
+
+        let mut data = read_at(&mut self.inner, offset, OBJECT_HEADER_SIZE)?;
+        let size = le64(&data, 8);
+        if data[0] != kind as u8
+            || size < kind.min_size()
+            || size > end - offset
+            || !self.is_valid_compression(kind, data[1])
+        {
+            return Err(invalid);
+        }
+        data.extend(read_at(
+            &mut self.inner,
+            offset + OBJECT_HEADER_SIZE,
+            size - OBJECT_HEADER_SIZE,
+        )?);
+        Ok(Object { offset, data })
+    }
+
+    /// Whether an object can have the given flags, which only data objects can have, and only
+    /// when the file has the incompatible flag for the codec.
+    fn is_valid_compression(&self, kind: ObjectType, flags: u8) -> bool {
+        let incompatible_flag = match flags {
+            0 => return true,
+            OBJECT_COMPRESSED_XZ => INCOMPATIBLE_COMPRESSED_XZ,
+            OBJECT_COMPRESSED_LZ4 => INCOMPATIBLE_COMPRESSED_LZ4,
+            OBJECT_COMPRESSED_ZSTD => INCOMPATIBLE_COMPRESSED_ZSTD,
+            _ => return false,
+        };
+        kind == ObjectType::Data && self.header.incompatible_flags & incompatible_flag != 0
+    }
+}
+
+impl<R> Iterator for JournalReader<R>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    type Item = Result<crate::export::ExportEntry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.read_entry().transpose();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
+
+/// Object is an object read from the arena, including its object header.
+struct Object {
+    offset: u64,
+    data: Vec<u8>,
+}
+
+impl Object {
+    /// Splits the payload of a data object into its field name and value.
+    fn field<'a>(&self, payload: &'a [u8]) -> Result<(&'a [u8], &'a [u8]), ParseError> {
+        let i = payload
+            .iter()
+            .position(|c| *c == b'=')
+            .ok_or_else(|| self.error(ParseErrorKind::InvalidField))?;
+        Ok((&payload[..i], &payload[i + 1..]))
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            offset: self.offset,
+            kind,
+        }
+    }
+}
+
+/// Decompresses the payload of a data object with the codec given by its flags, which have
+/// already been checked against the codecs that are enabled.  Returns `None` if the payload isn't
+/// valid, or decompresses to more than the journal allows.
+fn decompress(flags: u8, payload: &[u8]) -> Option<Vec<u8>> {
+    match flags {
+        #[cfg(feature = "xz")]
+        OBJECT_COMPRESSED_XZ => {
+            let mut output = LimitedWriter(Vec::new());
+            lzma_rs::xz_decompress(&mut &payload[..], &mut output).ok()?;
+            Some(output.0)
+        }
+        #[cfg(feature = "lz4")]
+        OBJECT_COMPRESSED_LZ4 => {
+            // systemd writes the length ahead of an LZ4 block, which can't expand by more than
+            // 255 times, so larger lengths are rejected before anything is allocated for them.
+            let len = le64(payload.get(..8)?, 0);
+            let block = &payload[8..];
+            if len > crate::export::VALUE_LEN_MAX || len > block.len() as u64 * LZ4_RATIO_MAX {
+                return None;
+            }
+            let output = lz4_flex::block::decompress(block, len as usize).ok()?;
+            (output.len() as u64 == len).then_some(output)
+        }
+        #[cfg(feature = "zstd")]
+        OBJECT_COMPRESSED_ZSTD => {
+            use std::io::Read;
+
+            let decoder = ruzstd::decoding::StreamingDecoder::new(payload).ok()?;
+            let mut output = Vec::new();
+            decoder
+                .take(crate::export::VALUE_LEN_MAX + 1)
+                .read_to_end(&mut output)
+                .ok()?;
+            (output.len() as u64 <= crate::export::VALUE_LEN_MAX).then_some(output)
+        }
+        0 => Some(payload.to_vec()),
+        _ => None,
+    }
+}
+
+/// LimitedWriter collects decompressed output, failing once it's longer than any value can be.
+#[cfg(feature = "xz")]
+struct LimitedWriter(Vec<u8>);
+
+#[cfg(feature = "xz")]
+impl std::io::Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if (self.0.len() + buf.len()) as u64 > crate::export::VALUE_LEN_MAX {
+            return Err(std::io::ErrorKind::OutOfMemory.into());
+        }
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads exactly `len` bytes at `offset`.
+fn read_at<R>(inner: &mut R, offset: u64, len: u64) -> Result<Vec<u8>, ParseError>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    use std::io::Read;
+
+    let error = |kind| ParseError { offset, kind };
+    inner
+        .seek(std::io::SeekFrom::Start(offset))
+        .map_err(|err| error(ParseErrorKind::Io(err)))?;
+    let mut data = Vec::new();
+    let n = inner
+        .by_ref()
+        .take(len)
+        .read_to_end(&mut data)
+        .map_err(|err| error(ParseErrorKind::Io(err)))?;
+    if (n as u64) < len {
+        return Err(error(ParseErrorKind::UnexpectedEof));
+    }
+    Ok(data)
+}
+
+fn le32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().expect("4 bytes"))
+}
+
+fn le64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().expect("8 bytes"))
+}
+
+fn id128(data: &[u8], at: usize) -> crate::id128::Id128 {
+    crate::id128::Id128::from_bytes(data[at..at + 16].try_into().expect("16 bytes"))
+}
+
+fn micros(value: u64) -> std::time::Duration {
+    std::time::Duration::from_micros(value)
+}
+
+/// SipHash-2-4, as used for the hash tables of files with keyed hashes, keyed by the file id.
+fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    use std::hash::Hasher;
+
+    let mut hasher = siphasher::sip::SipHasher24::new_with_key(key);
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// Bob Jenkins' lookup3 `hashlittle2`, with both halves combined, which is how the hash tables of
+/// files without keyed hashes are indexed.
+fn jenkins_hash64(data: &[u8]) -> u64 {
+    fn mix(a: &mut u32, b: &mut u32, c: &mut u32) {
+        *a = a.wrapping_sub(*c) ^ c.rotate_left(4);
+        *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a) ^ a.rotate_left(6);
+        *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b) ^ b.rotate_left(8);
+        *b = b.wrapping_add(*a);
+        *a = a.wrapping_sub(*c) ^ c.rotate_left(16);
+        *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a) ^ a.rotate_left(19);
+        *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b) ^ b.rotate_left(4);
+        *b = b.wrapping_add(*a);
+    }
+
+    fn finish(a: &mut u32, b: &mut u32, c: &mut u32) {
+        *c = (*c ^ *b).wrapping_sub(b.rotate_left(14));
+        *a = (*a ^ *c).wrapping_sub(c.rotate_left(11));
+        *b = (*b ^ *a).wrapping_sub(a.rotate_left(25));
+        *c = (*c ^ *b).wrapping_sub(b.rotate_left(16));
+        *a = (*a ^ *c).wrapping_sub(c.rotate_left(4));
+        *b = (*b ^ *a).wrapping_sub(a.rotate_left(14));
+        *c = (*c ^ *b).wrapping_sub(b.rotate_left(24));
+    }
+
+    let init = 0xdeadbeef_u32.wrapping_add(data.len() as u32);
+    let (mut a, mut b, mut c) = (init, init, init);
+    if !data.is_empty() {
+        let mut rest = data;
+        while rest.len() > 12 {
+            a = a.wrapping_add(le32(rest, 0));
+            b = b.wrapping_add(le32(rest, 4));
+            c = c.wrapping_add(le32(rest, 8));
+            mix(&mut a, &mut b, &mut c);
+            rest = &rest[12..];
+        }
+        let mut tail = [0; 12];
+        tail[..rest.len()].copy_from_slice(rest);
+        a = a.wrapping_add(le32(&tail, 0));
+        b = b.wrapping_add(le32(&tail, 4));
+        c = c.wrapping_add(le32(&tail, 8));
+        finish(&mut a, &mut b, &mut c);
+    }
+    (u64::from(c) << 32) | u64::from(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_SIZE: usize = 272;
+    const BUCKETS: usize = 4;
+    const BOOT_ID: crate::id128::Id128 =
+        crate::id128::Id128::from_u128(0x5e727be24f9240eb8c8f3d3ae430c3f6);
+    const SEQNUM_ID: crate::id128::Id128 =
+        crate::id128::Id128::from_u128(0x91983bbac2e545e89314cf0275e19d5a);
+    const FILE_ID: [u8; 16] = [1; 16];
+
+    /// Builder lays out a journal file the way journald does, for the reader to read back.
+    struct Builder {
+        flags: u32,
+        data: Vec<u8>,
+        field_table: u64,
+        data_table: u64,
+        fields: Vec<(Vec<u8>, u64)>,
+        payloads: Vec<(Vec<u8>, (u64, u64))>,
+        entries: Vec<(u64, u64)>,
+        n_objects: u64,
+        tail_object: u64,
+    }
+
+    impl Builder {
+        fn new(flags: u32) -> Self {
+            let mut builder = Self {
+                flags,
+                data: vec![0; HEADER_SIZE],
+                field_table: 0,
+                data_table: 0,
+                fields: Vec::new(),
+                payloads: Vec::new(),
+                entries: Vec::new(),
+                n_objects: 0,
+                tail_object: 0,
+            };
+            builder.field_table =
+                builder.append(ObjectType::FieldHashTable as u8, 0, &[0; 16 * BUCKETS]);
+            builder.data_table = builder.append(4, 0, &[0; 16 * BUCKETS]);
+            builder
+        }
+
+        fn put(&mut self, at: u64, value: u64) {
+            let at = at as usize;
+            self.data[at..at + 8].copy_from_slice(&value.to_le_bytes());
+        }
+
+        fn get(&self, at: u64) -> u64 {
+            le64(&self.data, at as usize)
+        }
+
+        fn append(&mut self, kind: u8, flags: u8, body: &[u8]) -> u64 {
+            let offset = self.data.len() as u64;
+            self.data.extend([kind, flags, 0, 0, 0, 0, 0, 0]);
+            self.data.extend((body.len() as u64 + 16).to_le_bytes());
+            self.data.extend(body);
+            self.data.resize(self.data.len().next_multiple_of(8), 0);
+            self.n_objects += 1;
+            self.tail_object = offset;
+            offset
+        }
+
+        fn hash(&self, data: &[u8]) -> u64 {
+            if self.flags & INCOMPATIBLE_KEYED_HASH != 0 {
+                siphash24(&FILE_ID, data)
+            } else {
+                jenkins_hash64(data)
+            }
+        }
+
+        /// Offsets are written in 32 bits in compact files.
+        fn offset(&self, offset: u64) -> Vec<u8> {
+            if self.flags & INCOMPATIBLE_COMPACT != 0 {
+                (offset as u32).to_le_bytes().to_vec()
+            } else {
+                offset.to_le_bytes().to_vec()
+            }
+        }
+
+        /// Compresses a payload with the codec given by the file's flags, if any.
+        fn compress(&self, payload: &[u8]) -> (u8, Vec<u8>) {
+            let codecs = INCOMPATIBLE_COMPRESSED_XZ
+                | INCOMPATIBLE_COMPRESSED_LZ4
+                | INCOMPATIBLE_COMPRESSED_ZSTD;
+            match self.flags & codecs {
+                #[cfg(feature = "xz")]
+                INCOMPATIBLE_COMPRESSED_XZ => {
+                    let mut output = Vec::new();
+                    lzma_rs::xz_compress(&mut &payload[..], &mut output).unwrap();
+                    (OBJECT_COMPRESSED_XZ, output)
+                }
+                #[cfg(feature = "lz4")]
+                INCOMPATIBLE_COMPRESSED_LZ4 => {
+                    let mut output = (payload.len() as u64).to_le_bytes().to_vec();
+                    output.extend(lz4_flex::block::compress(payload));
+                    (OBJECT_COMPRESSED_LZ4, output)
+                }
+                // Unlike journald's, these frames leave out the content size, which only systemd
+                // itself insists on.
+                #[cfg(feature = "zstd")]
+                INCOMPATIBLE_COMPRESSED_ZSTD => {
+                    let level = ruzstd::encoding::CompressionLevel::Fastest;
+                    let output = ruzstd::encoding::compress_to_vec(payload, level);
+                    (OBJECT_COMPRESSED_ZSTD, output)
+                }
+                _ => (0, payload.to_vec()),
+            }
+        }
+
+        /// Links an object into a hash table bucket, through the next offset at `next`.
+        fn link(&mut self, table: u64, hash: u64, offset: u64, next: u64) {
+            let item = table + 16 + 16 * (hash % BUCKETS as u64);
+            match self.get(item + 8) {
+                0 => self.put(item, offset),
+                tail => self.put(tail + next, offset),
+            }
+            self.put(item + 8, offset);
+        }
+
+        fn field(&mut self, name: &[u8]) -> u64 {
+            if let Some((_, offset)) = self.fields.iter().find(|(field, _)| field == name) {
+                return *offset;
+            }
+            let hash = self.hash(name);
+            let mut body = hash.to_le_bytes().to_vec();
+            body.extend([0; 16]);
+            body.extend(name);
+            let offset = self.append(ObjectType::Field as u8, 0, &body);
+            self.link(self.field_table, hash, offset, 24);
+            self.fields.push((name.to_vec(), offset));
+            offset
+        }
+
+        fn data(&mut self, payload: &[u8]) -> (u64, u64) {
+            if let Some((_, data)) = self.payloads.iter().find(|(data, _)| data == payload) {
+                return *data;
+            }
+            let name = payload.split(|c| *c == b'=').next().unwrap();
+            let field = self.field(name);
+            let hash = self.hash(payload);
+            let mut body = hash.to_le_bytes().to_vec();
+            body.extend([0; 8]);
+            body.extend(self.get(field + 32).to_le_bytes());
+            body.extend([0; 24]);
+            if self.flags & INCOMPATIBLE_COMPACT != 0 {
+                body.extend([0; 8]);
+            }
+            let (flags, compressed) = self.compress(payload);
+            body.extend(compressed);
+            let offset = self.append(ObjectType::Data as u8, flags, &body);
+            self.link(self.data_table, hash, offset, 24);
+            self.put(field + 32, offset);
+            self.payloads.push((payload.to_vec(), (offset, hash)));
+            (offset, hash)
+        }
+
+        fn entry(&mut self, realtime: u64, payloads: &[&[u8]]) -> u64 {
+            let items: Vec<_> = payloads.iter().map(|payload| self.data(payload)).collect();
+            let seqnum = self.entries.len() as u64 + 1;
+            let mut body = Vec::new();
+            body.extend(seqnum.to_le_bytes());
+            body.extend(realtime.to_le_bytes());
+            body.extend((realtime % 1_000_000_000).to_le_bytes());
+            body.extend(BOOT_ID.as_bytes());
+            // Unlike the hash tables, xor_hash is never keyed.
+            let xor_hash = payloads
+                .iter()
+                .fold(0, |xor, payload| xor ^ jenkins_hash64(payload));
+            body.extend(xor_hash.to_le_bytes());
+            for (offset, hash) in items.iter().copied() {
+                body.extend(self.offset(offset));
+                if self.flags & INCOMPATIBLE_COMPACT == 0 {
+                    body.extend(hash.to_le_bytes());
+                }
+            }
+            let offset = self.append(ObjectType::Entry as u8, 0, &body);
+            self.entries.push((offset, realtime));
+            for (data, _) in items {
+                self.link_entry(data, offset);
+            }
+            offset
+        }
+
+        /// Adds an entry to those of a data object, the first in the object itself, and the rest
+        /// in its chain of entry arrays, which are given one item each.
+        fn link_entry(&mut self, data: u64, entry: u64) {
+            let n_entries = self.get(data + 56);
+            self.put(data + 56, n_entries + 1);
+            if n_entries == 0 {
+                self.put(data + 40, entry);
+                return;
+            }
+            let mut body = vec![0; 8];
+            body.extend(self.offset(entry));
+            let array = self.append(ObjectType::EntryArray as u8, 0, &body);
+            let mut next = data + 48;
+            while self.get(next) != 0 {
+                next = self.get(next) + 16;
+            }
+            self.put(next, array);
+        }
+
+        /// Links the entries from a chain of arrays with room for `capacities` entries each.
+        fn finish(mut self, capacities: &[usize]) -> Vec<u8> {
+            let mut entries = self.entries.clone().into_iter();
+            let mut head = 0;
+            let mut previous = 0;
+            for capacity in capacities {
+                let mut body = vec![0; 8];
+                for _ in 0..*capacity {
+                    body.extend(self.offset(entries.next().map_or(0, |(offset, _)| offset)));
+                }
+                let offset = self.append(ObjectType::EntryArray as u8, 0, &body);
+                match previous {
+                    0 => head = offset,
+                    previous => self.put(previous + 16, offset),
+                }
+                previous = offset;
+            }
+
+            let header = &mut self.data[..HEADER_SIZE];
+            header[..8].copy_from_slice(SIGNATURE);
+            header[12..16].copy_from_slice(&self.flags.to_le_bytes());
+            header[24..40].copy_from_slice(&FILE_ID);
+            header[40..56].copy_from_slice(&[2; 16]);
+            header[56..72].copy_from_slice(BOOT_ID.as_bytes());
+            header[72..88].copy_from_slice(SEQNUM_ID.as_bytes());
+            let len = self.data.len() as u64;
+            let realtime = |entry: Option<&(u64, u64)>| entry.map_or(0, |(_, realtime)| *realtime);
+            let values = [
+                (88, HEADER_SIZE as u64),
+                (96, len - HEADER_SIZE as u64),
+                (104, self.data_table + 16),
+                (112, 16 * BUCKETS as u64),
+                (120, self.field_table + 16),
+                (128, 16 * BUCKETS as u64),
+                (136, self.tail_object),
+                (144, self.n_objects),
+                (152, self.entries.len() as u64),
+                (160, self.entries.len() as u64),
+                (168, 1),
+                (176, head),
+                (184, realtime(self.entries.first())),
+                (192, realtime(self.entries.last())),
+                (200, realtime(self.entries.last()) % 1_000_000_000),
+            ];
+            for (at, value) in values {
+                self.put(at, value);
+            }
+            self.data
+        }
+    }
+
+    fn reader(data: Vec<u8>) -> JournalReader<std::io::Cursor<Vec<u8>>> {
+        JournalReader::new(std::io::Cursor::new(data)).unwrap()
+    }
+
+    fn error(data: Vec<u8>) -> ParseError {
+        let mut reader = match JournalReader::new(std::io::Cursor::new(data)) {
+            Ok(reader) => reader,
+            Err(err) => return err,
+        };
+        reader.find_map(Result::err).expect("an error")
+    }
+
+    fn example(flags: u32) -> Builder {
+        let mut builder = Builder::new(flags);
+        builder.entry(
+            1_700_000_123_000_000,
+            &[
+                b"_BOOT_ID=5e727be24f9240eb8c8f3d3ae430c3f6",
+                b"MESSAGE=first",
+                b"TAG=a",
+            ],
+        );
+        builder.entry(1_700_000_123_000_001, &[b"MESSAGE=second\n\x01", b"_PID=1"]);
+        builder.entry(
+            1_700_000_123_000_002,
+            &[b"MESSAGE=third", b"TAG=a", b"TAG=b"],
+        );
+        builder
+    }
+
+    #[test]
+    fn test_jenkins_hash64() {
+        assert_eq!(jenkins_hash64(b""), 0xdeadbeef_deadbeef);
+        assert_eq!(
+            jenkins_hash64(b"Four score and seven years ago"),
+            0x17770551_ce7226e6
+        );
+    }
+
+    #[test]
+    fn test_siphash24() {
+        let key: Vec<u8> = (0..16).collect();
+        let key = key.try_into().unwrap();
+        assert_eq!(siphash24(&key, b""), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(&key, &[0, 1, 2, 3, 4, 5, 6]), 0xab0200f58b01d137);
+    }
+
+    #[test]
+    fn test_read_entries() {
+        let mut reader = reader(example(0).finish(&[1, 4]));
+        assert_eq!(reader.header().state(), State::Offline);
+        assert_eq!(reader.header().seqnum_id(), SEQNUM_ID);
+        assert_eq!(reader.header().n_entries(), 3);
+
+        let first = reader.next().unwrap().unwrap();
+        let address = first.address().unwrap();
+        assert_eq!(
+            address.realtime(),
+            std::time::UNIX_EPOCH + micros(1_700_000_123_000_000)
+        );
+        assert_eq!(address.monotonic(), Some((BOOT_ID, micros(123_000_000))));
+        assert!(address
+            .cursor()
+            .unwrap()
+            .starts_with("s=91983bbac2e545e89314cf0275e19d5a;i=1;b=5e727be24f9240eb"));
+        assert_eq!(
+            first.protected().collect::<Vec<_>>(),
+            [("_BOOT_ID", &b"5e727be24f9240eb8c8f3d3ae430c3f6"[..])]
+        );
+        assert_eq!(first.get(crate::raw::MESSAGE), Some(&b"first"[..]));
+
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second.get(crate::raw::MESSAGE), Some(&b"second\n\x01"[..]));
+        assert_eq!(
+            second.protected().collect::<Vec<_>>(),
+            [("_PID", &b"1"[..])]
+        );
+
+        let third = reader.next().unwrap().unwrap();
+        let tag = crate::raw::Field::validate("TAG").unwrap();
+        assert_eq!(third.get_all(tag).collect::<Vec<_>>(), [b"a", b"b"]);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_read_unused_items() {
+        // Items past the number of entries in the header are never read.
+        let mut data = example(0).finish(&[8]);
+        let mut reader = reader(data.clone());
+        assert_eq!(reader.by_ref().count(), 3);
+
+        data[152..160].copy_from_slice(&2u64.to_le_bytes());
+        assert_eq!(self::reader(data).count(), 2);
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut reader = reader(example(0).finish(&[4]));
+        assert_eq!(
+            reader.fields().unwrap(),
+            ["MESSAGE", "TAG", "_BOOT_ID", "_PID"]
+        );
+
+        let mut values = reader.unique_values("TAG").unwrap();
+        values.sort();
+        assert_eq!(values, [b"a", b"b"]);
+        assert!(reader.unique_values("MISSING").unwrap().is_empty());
+    }
+
+    /// Reads every entry of a file, along with the values of `TAG` found through the hash tables.
+    fn read_all(data: Vec<u8>) -> (Vec<crate::export::ExportEntry>, Vec<Vec<u8>>) {
+        let mut reader = reader(data);
+        let mut values = reader.unique_values("TAG").unwrap();
+        values.sort();
+        (reader.map(Result::unwrap).collect(), values)
+    }
+
+    #[test]
+    fn test_compact_keyed_hash() {
+        let expected = read_all(example(0).finish(&[1, 4]));
+        for flags in [
+            INCOMPATIBLE_COMPACT,
+            INCOMPATIBLE_KEYED_HASH,
+            INCOMPATIBLE_COMPACT | INCOMPATIBLE_KEYED_HASH,
+        ] {
+            let data = example(flags).finish(&[1, 4]);
+            let header = Header::parse(&data).unwrap();
+            assert_eq!(header.is_compact(), flags & INCOMPATIBLE_COMPACT != 0);
+            assert_eq!(
+                header.has_keyed_hash(),
+                flags & INCOMPATIBLE_KEYED_HASH != 0
+            );
+            assert_eq!(read_all(data), expected);
+        }
+    }
+
+    #[test]
+    fn test_compressed() {
+        let expected = read_all(example(0).finish(&[4]));
+        let codecs = [
+            (cfg!(feature = "xz"), INCOMPATIBLE_COMPRESSED_XZ),
+            (cfg!(feature = "lz4"), INCOMPATIBLE_COMPRESSED_LZ4),
+            (cfg!(feature = "zstd"), INCOMPATIBLE_COMPRESSED_ZSTD),
+        ];
+        for (enabled, codec) in codecs {
+            let data = example(codec | INCOMPATIBLE_COMPACT).finish(&[4]);
+            if !enabled {
+                assert!(matches!(
+                    error(data).kind(),
+                    ParseErrorKind::UnsupportedFlags(flags) if *flags == codec
+                ));
+                continue;
+            }
+            assert_eq!(read_all(data), expected);
+
+            // Damage the payload of the first data object, _BOOT_ID.
+            let mut builder = example(codec);
+            let (offset, _) = builder.data(b"_BOOT_ID=5e727be24f9240eb8c8f3d3ae430c3f6");
+            let mut data = builder.finish(&[4]);
+            data[offset as usize + 64..offset as usize + 72].fill(0xff);
+            let err = error(data);
+            assert!(matches!(err.kind(), ParseErrorKind::InvalidCompression));
+            assert_eq!(err.offset(), offset);
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_len() {
+        let block = lz4_flex::block::compress(&[b'a'; 1024]);
+        let payload = |len: u64| [len.to_le_bytes().as_slice(), &block].concat();
+        assert_eq!(
+            decompress(OBJECT_COMPRESSED_LZ4, &payload(1024)),
+            Some(vec![b'a'; 1024])
+        );
+        // The length is checked against what the block could expand to, not only the limit.
+        let len = block.len() as u64 * LZ4_RATIO_MAX + 1;
+        assert!(len < crate::export::VALUE_LEN_MAX);
+        assert_eq!(decompress(OBJECT_COMPRESSED_LZ4, &payload(len)), None);
+    }
+
+    fn messages<R>(reader: &mut JournalReader<R>) -> Vec<Vec<u8>>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        reader
+            .map(|entry| entry.unwrap().get(crate::raw::MESSAGE).unwrap().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        let tag = crate::raw::Field::validate("TAG").unwrap();
+        for flags in [0, INCOMPATIBLE_COMPACT | INCOMPATIBLE_KEYED_HASH] {
+            let mut reader = reader(example(flags).finish(&[1, 4]));
+            let mut set = |matches: &crate::matches::Matches| {
+                reader.set_matches(matches).unwrap();
+                messages(&mut reader)
+            };
+
+            let mut matches = crate::matches::Matches::new();
+            matches.add_match(tag, "a");
+            assert_eq!(set(&matches), [&b"first"[..], b"third"]);
+
+            matches.add_match(crate::raw::MESSAGE, "third");
+            assert_eq!(set(&matches), [b"third"]);
+
+            let parsed = crate::matches::Matches::parse(["TAG=a", "+", "_PID=1"]).unwrap();
+            assert_eq!(set(&parsed), [&b"first"[..], b"second\n\x01", b"third"]);
+
+            let missing = crate::matches::Matches::parse(["TAG=c", "+", "_PID=2"]).unwrap();
+            assert!(set(&missing).is_empty());
+
+            assert_eq!(set(&crate::matches::Matches::new()).len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let data = example(0).finish(&[4]);
+
+        let mut invalid = data.clone();
+        invalid[0] = b'X';
+        assert!(matches!(
+            error(invalid).kind(),
+            ParseErrorKind::InvalidHeader
+        ));
+
+        let mut unsupported = data.clone();
+        unsupported[12] = 0x31;
+        let flags = 0x20 | if cfg!(feature = "xz") { 0 } else { 0x1 };
+        assert!(matches!(
+            error(unsupported).kind(),
+            ParseErrorKind::UnsupportedFlags(unsupported) if *unsupported == flags
+        ));
+
+        let truncated = data[..data.len() - 8].to_vec();
+        assert!(matches!(
+            error(truncated).kind(),
+            ParseErrorKind::InvalidHeader
+        ));
+
+        // Point the entry array at the first entry instead.
+        let builder = example(0);
+        let entry = builder.entries[0].0;
+        let mut wrong_type = builder.finish(&[4]);
+        wrong_type[176..184].copy_from_slice(&entry.to_le_bytes());
+        let err = error(wrong_type);
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidObject));
+        assert_eq!(err.offset(), entry);
+        assert_eq!(err.to_string(), format!("byte {}: invalid object", entry));
+    }
+}
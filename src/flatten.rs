@@ -0,0 +1,339 @@
+//! Flattening of nested keys, such as `http.status` or `db::query`, into field names.
+//!
+//! The scheme is deterministic, so the same key always becomes the same field:
+//!
+//! * The key is split into segments at `.`, `/` and `:`, with empty segments dropped, so both
+//!   `db.query` and `db::query` have the segments `db` and `query`.
+//! * Within a segment, ASCII letters are uppercased, digits and `_` are kept, and anything else
+//!   becomes `_`.
+//! * Segments are joined by `__`, e.g. `http.status` becomes `HTTP__STATUS` while `http_status`
+//!   stays `HTTP_STATUS`.
+//! * Without a prefix, leading characters that aren't letters are dropped, since a field has to
+//!   start with one.  The name is then truncated to the 64 characters that the journal allows.
+//!
+//! [[`unflatten`]] maps a field name back to its key.  This is exact for keys whose segments are
+//! made of lowercase letters, digits and underscores, start with a letter, and neither end with
+//! an underscore nor have two in a row, which is the common style of both `slog` and `log` keys,
+//! as long as the name wasn't truncated.  Otherwise underscores can run into the separator, e.g.
+//! `a_.b` becomes `A___B`, which maps back to `a._b`, and a truncated name only maps back to the
+//! start of its key.  Other keys still flatten deterministically, but distinct keys may then
+//! share a name, e.g. `user-id` and `user_id`, or two long keys that only differ after the 64th
+//! character.  [[`FieldNames`]] detects these collisions within an entry and either renames or
+//! drops the later key.
+//!
+//! ```
+//! use journaled::flatten::{flatten, unflatten};
+//!
+//! let field = flatten("db.query.ms").unwrap();
+//! assert_eq!(field.as_str(), "DB__QUERY__MS");
+//! assert_eq!(unflatten((&field).into()), "db.query.ms");
+//! ```
+
+const SEPARATOR: &str = "__";
+
+/// Collision decides what happens to a key whose field name is already used within an entry.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Collision {
+    /// Appends `_2`, `_3` and so on to the name until it's unique, e.g. `HTTP_STATUS_2`.
+    #[default]
+    Suffix,
+    /// Drops the value, keeping the first one that used the name.
+    Skip,
+}
+
+/// Flattens a key into a field name, returning `None` if it contains no letters.
+pub fn flatten(key: &str) -> Option<crate::raw::OwnedField> {
+    flatten_path(segments(key))
+}
+
+/// Flattens a key that's already split into segments, e.g. the path to a nested value.
+///
+/// Each segment is still split further at `.`, `/` and `:`.
+pub fn flatten_path<'k, I>(path: I) -> Option<crate::raw::OwnedField>
+where
+    I: IntoIterator<Item = &'k str>,
+{
+    let name = join(path);
+    let name = name.trim_start_matches(|c: char| !c.is_ascii_alphabetic());
+    crate::raw::OwnedField::validate(truncate(name, crate::raw::FIELD_LEN_MAX).to_owned())
+}
+
+/// Flattens a key into a field name that starts with `prefix`, returning `None` if the key has no
+/// segments.
+pub fn flatten_with_prefix(key: &str, prefix: crate::raw::Field) -> Option<crate::raw::OwnedField> {
+    prefixed(join(segments(key)), prefix)
+}
+
+fn prefixed(name: String, prefix: crate::raw::Field) -> Option<crate::raw::OwnedField> {
+    if name.is_empty() {
+        return None;
+    }
+    let name = truncate(&name, crate::raw::FIELD_LEN_MAX - prefix.as_str().len());
+    crate::raw::OwnedField::validate(format!("{}{}", prefix.as_str(), name))
+}
+
+/// Maps a field name back to the key it was flattened from, e.g. `DB__QUERY__MS` to
+/// `db.query.ms`.
+pub fn unflatten(field: crate::raw::Field) -> String {
+    field
+        .as_str()
+        .split(SEPARATOR)
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Maps a field name back to its key, as [[`unflatten`]], if it starts with `prefix`.
+pub fn unflatten_with_prefix(
+    field: crate::raw::Field,
+    prefix: crate::raw::Field,
+) -> Option<String> {
+    let name = field.as_str().strip_prefix(prefix.as_str())?;
+    crate::raw::Field::validate(name).map(unflatten)
+}
+
+fn segments(key: &str) -> impl Iterator<Item = &str> {
+    key.split(['.', '/', ':'])
+        .filter(|segment| !segment.is_empty())
+}
+
+fn join<'k, I>(path: I) -> String
+where
+    I: IntoIterator<Item = &'k str>,
+{
+    let mut name = String::new();
+    for segment in path.into_iter().flat_map(segments) {
+        if !name.is_empty() {
+            name.push_str(SEPARATOR);
+        }
+        name.extend(segment.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        }));
+    }
+    name
+}
+
+/// Shortens an ASCII name to at most `len` characters.
+fn truncate(name: &str, len: usize) -> &str {
+    &name[..name.len().min(len)]
+}
+
+/// FieldNames assigns unique field names to the keys of a single entry.
+///
+/// Names are handed out in order, so the first key to flatten to a name keeps it and later ones
+/// are renamed or dropped according to the [[`Collision`]] policy.  Names that are written by
+/// other means, such as `MESSAGE`, can be reserved up front so that keys never reuse them.
+///
+/// ```
+/// use journaled::flatten::{Collision, FieldNames};
+///
+/// let mut names = FieldNames::new(Collision::Suffix);
+/// names.reserve(journaled::raw::MESSAGE);
+///
+/// let names: Vec<_> = ["http.status", "http.status", "message"]
+///     .into_iter()
+///     .filter_map(|key| names.name(key))
+///     .collect();
+/// assert_eq!(names[0].as_str(), "HTTP__STATUS");
+/// assert_eq!(names[1].as_str(), "HTTP__STATUS_2");
+/// assert_eq!(names[2].as_str(), "MESSAGE_2");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FieldNames {
+    prefix: Option<crate::raw::OwnedField>,
+    collision: Collision,
+    used: std::collections::HashSet<crate::raw::OwnedField>,
+}
+
+impl FieldNames {
+    pub fn new(collision: Collision) -> Self {
+        Self {
+            collision,
+            ..Self::default()
+        }
+    }
+
+    /// Prefixes every name, as by [[`flatten_with_prefix`]].
+    pub fn with_prefix(mut self, prefix: crate::raw::Field) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Marks a name as used, so that keys flattening to it are treated as collisions.
+    pub fn reserve<'a, F>(&mut self, field: F)
+    where
+        F: Into<crate::raw::Field<'a>>,
+    {
+        self.used.insert(field.into().into());
+    }
+
+    /// Flattens a key without checking whether its name is used.
+    pub fn flatten(&self, key: &str) -> Option<crate::raw::OwnedField> {
+        self.flatten_path(segments(key))
+    }
+
+    /// Flattens a key that's already split into segments, as by [[`flatten_path`]], without
+    /// checking whether its name is used.
+    pub fn flatten_path<'k, I>(&self, path: I) -> Option<crate::raw::OwnedField>
+    where
+        I: IntoIterator<Item = &'k str>,
+    {
+        match &self.prefix {
+            Some(prefix) => prefixed(join(path), prefix.into()),
+            None => flatten_path(path),
+        }
+    }
+
+    /// Flattens a key into a name that's unique within the entry, returning `None` if the key
+    /// can't be flattened or is dropped because of a collision.
+    pub fn name(&mut self, key: &str) -> Option<crate::raw::OwnedField> {
+        self.flatten(key).and_then(|field| self.claim(field))
+    }
+
+    /// Uses a name that was already flattened, e.g. after it was renamed, applying the collision
+    /// policy if it's already used.
+    pub fn claim(&mut self, field: crate::raw::OwnedField) -> Option<crate::raw::OwnedField> {
+        let field = if self.used.contains(&field) {
+            match self.collision {
+                Collision::Suffix => self.suffixed(&field),
+                Collision::Skip => return None,
+            }
+        } else {
+            field
+        };
+        self.used.insert(field.clone());
+        Some(field)
+    }
+
+    fn suffixed(&self, field: &crate::raw::OwnedField) -> crate::raw::OwnedField {
+        (2..)
+            .filter_map(|n: usize| {
+                let suffix = format!("_{}", n);
+                let name = truncate(
+                    field.as_str(),
+                    crate::raw::FIELD_LEN_MAX.saturating_sub(suffix.len()),
+                );
+                crate::raw::OwnedField::validate(format!("{}{}", name, suffix))
+            })
+            .find(|field| !self.used.contains(field))
+            .expect("a free suffix exists")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(key: &str) -> Option<String> {
+        flatten(key).map(|field| field.as_str().to_owned())
+    }
+
+    #[test]
+    fn test_flatten() {
+        assert_eq!(flat("user_id").as_deref(), Some("USER_ID"));
+        assert_eq!(flat("http.status").as_deref(), Some("HTTP__STATUS"));
+        assert_eq!(flat("db::query.ms").as_deref(), Some("DB__QUERY__MS"));
+        assert_eq!(flat("a/b//c").as_deref(), Some("A__B__C"));
+        assert_eq!(flat("content-type").as_deref(), Some("CONTENT_TYPE"));
+        assert_eq!(flat("_private.x").as_deref(), Some("PRIVATE__X"));
+        assert_eq!(flat("2fa").as_deref(), Some("FA"));
+        assert_eq!(flat("..."), None);
+        assert_eq!(flat("123"), None);
+        assert_eq!(flat(&"a".repeat(100)).unwrap().len(), 64);
+
+        assert_eq!(
+            flatten_path(["request", "headers.host"]).unwrap().as_str(),
+            "REQUEST__HEADERS__HOST"
+        );
+    }
+
+    #[test]
+    fn test_flatten_with_prefix() {
+        let prefix = crate::raw::Field::validate("APP_").unwrap();
+        assert_eq!(
+            flatten_with_prefix("http.status", prefix).unwrap().as_str(),
+            "APP_HTTP__STATUS"
+        );
+        assert_eq!(flatten_with_prefix("_", prefix).unwrap().as_str(), "APP__");
+        assert_eq!(flatten_with_prefix("..", prefix), None);
+        assert_eq!(
+            flatten_with_prefix(&"a".repeat(100), prefix)
+                .unwrap()
+                .as_str()
+                .len(),
+            64
+        );
+    }
+
+    #[test]
+    fn test_unflatten() {
+        for key in ["user_id", "http.status", "db.query.ms", "v2.a1_b"] {
+            let field = flatten(key).unwrap();
+            assert_eq!(unflatten((&field).into()), key);
+        }
+
+        for key in ["a_.b", "a__b"] {
+            let field = flatten(key).unwrap();
+            assert_ne!(unflatten((&field).into()), key);
+        }
+
+        let prefix = crate::raw::Field::validate("APP_").unwrap();
+        let field = flatten_with_prefix("http.status", prefix).unwrap();
+        assert_eq!(
+            unflatten_with_prefix((&field).into(), prefix).as_deref(),
+            Some("http.status")
+        );
+        assert_eq!(unflatten_with_prefix(crate::raw::MESSAGE, prefix), None);
+    }
+
+    #[test]
+    fn test_field_names() {
+        let mut names = FieldNames::new(Collision::Suffix);
+        names.reserve(crate::raw::MESSAGE);
+        let mut name = |key| names.name(key).map(|field| field.as_str().to_owned());
+        assert_eq!(name("user-id").as_deref(), Some("USER_ID"));
+        assert_eq!(name("user_id").as_deref(), Some("USER_ID_2"));
+        assert_eq!(name("user.id_2").as_deref(), Some("USER__ID_2"));
+        assert_eq!(name("user id").as_deref(), Some("USER_ID_3"));
+        assert_eq!(name("message").as_deref(), Some("MESSAGE_2"));
+
+        let mut names = FieldNames::new(Collision::Skip);
+        assert!(names.name("user_id").is_some());
+        assert!(names.name("user-id").is_none());
+
+        let mut names = FieldNames::new(Collision::Suffix);
+        let long = "A".repeat(64);
+        assert_eq!(names.name(&long).unwrap().as_str(), long);
+        assert_eq!(
+            names.name(&long).unwrap().as_str(),
+            format!("{}_2", &long[..62])
+        );
+    }
+
+    #[test]
+    fn test_truncated_collision() {
+        // Both keys truncate to the same 64 characters.
+        let prefix = "request.headers.".repeat(4);
+        let first = format!("{}user_agent", prefix);
+        let second = format!("{}accept", prefix);
+        let truncated = flatten(&first).unwrap();
+        assert_eq!(truncated.as_str().len(), crate::raw::FIELD_LEN_MAX);
+        assert_eq!(flatten(&second).unwrap(), truncated);
+        assert_ne!(unflatten((&truncated).into()), first);
+
+        let mut names = FieldNames::new(Collision::Suffix);
+        assert_eq!(names.name(&first).unwrap(), truncated);
+        assert_eq!(
+            names.name(&second).unwrap().as_str(),
+            format!("{}_2", &truncated.as_str()[..62])
+        );
+
+        let mut names = FieldNames::new(Collision::Skip);
+        assert!(names.name(&first).is_some());
+        assert!(names.name(&second).is_none());
+    }
+}
@@ -0,0 +1,285 @@
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const RANDOM_PATH: &str = "/dev/urandom";
+
+/// Decodes a single ASCII hex digit, accepting both upper and lower case.
+const fn decode_hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Id128 is a 128-bit identifier, as used by systemd for message, boot, and machine ids.
+///
+/// The canonical text form is 32 lowercase hex digits, which is what journald and `journalctl`
+/// expect in fields such as `MESSAGE_ID`.  The dashed UUID form is accepted when parsing.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Id128 {
+    bytes: [u8; 16],
+}
+
+impl Id128 {
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self { bytes }
+    }
+
+    /// Constructs an id from its big-endian integer representation, such that the hex digits
+    /// appear in the same order as when formatted.
+    pub const fn from_u128(value: u128) -> Self {
+        Self {
+            bytes: value.to_be_bytes(),
+        }
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; 16] {
+        &self.bytes
+    }
+
+    pub const fn as_u128(&self) -> u128 {
+        u128::from_be_bytes(self.bytes)
+    }
+
+    /// Parses either the plain 32 hex digit form or the 36 character UUID form.
+    ///
+    /// This is const so that ids can be declared at compile time, see [[`crate::message_id`]].
+    pub const fn parse(s: &str) -> Option<Self> {
+        let s = s.as_bytes();
+        let dashed = match s.len() {
+            32 => false,
+            36 => true,
+            _ => return None,
+        };
+
+        let mut bytes = [0u8; 16];
+        let mut i = 0;
+        let mut n = 0;
+        while n < 16 {
+            if dashed && (i == 8 || i == 13 || i == 18 || i == 23) {
+                if s[i] != b'-' {
+                    return None;
+                }
+                i += 1;
+            }
+            let (hi, lo) = match (decode_hex_digit(s[i]), decode_hex_digit(s[i + 1])) {
+                (Some(hi), Some(lo)) => (hi, lo),
+                _ => return None,
+            };
+            bytes[n] = (hi << 4) | lo;
+            i += 2;
+            n += 1;
+        }
+        Some(Self { bytes })
+    }
+
+    /// Generates a random id, formatted as a version 4 UUID in the same way as
+    /// `systemd-id128 new` or `journalctl --new-id128`.
+    pub fn random() -> std::io::Result<Self> {
+        use std::io::Read;
+
+        let mut bytes = [0u8; 16];
+        std::fs::File::open(RANDOM_PATH)?.read_exact(&mut bytes)?;
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Ok(Self { bytes })
+    }
+
+    /// Encodes the id as 32 lowercase hex digits.
+    const fn encode(&self) -> [u8; 32] {
+        let mut hex = [0u8; 32];
+        let mut n = 0;
+        while n < 16 {
+            hex[2 * n] = HEX_DIGITS[(self.bytes[n] >> 4) as usize];
+            hex[2 * n + 1] = HEX_DIGITS[(self.bytes[n] & 0x0f) as usize];
+            n += 1;
+        }
+        hex
+    }
+
+    /// Formats the id in the dashed UUID form, e.g. `6bb4d5e8-1ea1-4a3b-9a4e-77d8f1b3bf0e`.
+    pub fn to_uuid_string(&self) -> String {
+        let hex = self.encode();
+        let hex = std::str::from_utf8(&hex).expect("hex digits are ascii");
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl std::fmt::Display for Id128 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = self.encode();
+        f.write_str(std::str::from_utf8(&hex).expect("hex digits are ascii"))
+    }
+}
+
+impl std::str::FromStr for Id128 {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or(ParseIdError)
+    }
+}
+
+impl std::convert::From<[u8; 16]> for Id128 {
+    fn from(bytes: [u8; 16]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+/// Error returned when a string is not a valid 128-bit id.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseIdError;
+
+impl std::fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("expected 32 hex digits or a UUID")
+    }
+}
+
+impl std::error::Error for ParseIdError {}
+
+/// MessageId is an [[`Id128`]] used as the `MESSAGE_ID` of an entry.
+///
+/// The formatted value is kept alongside the id, so a MessageId can be passed to
+/// [[`crate::raw::JournalWriter::send`]] without formatting it on every call.  Entries sent with a
+/// MessageId can then be filtered with `journalctl MESSAGE_ID=<id>`, and explained by a catalog.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct MessageId {
+    id: Id128,
+    hex: [u8; 32],
+}
+
+impl MessageId {
+    pub const fn new(id: Id128) -> Self {
+        Self {
+            id,
+            hex: id.encode(),
+        }
+    }
+
+    /// Parses an id at compile time, panicking if it's invalid.  Used by [[`crate::message_id`]].
+    pub const fn parse_const(s: &str) -> Self {
+        match Id128::parse(s) {
+            Some(id) => Self::new(id),
+            None => panic!("invalid message id, expected 32 hex digits or a UUID"),
+        }
+    }
+
+    pub const fn id(&self) -> Id128 {
+        self.id
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.hex).expect("hex digits are ascii")
+    }
+
+    /// Is used to construct a tuple to be passed into [[`crate::raw::JournalWriter`]].
+    pub fn as_value(&self) -> (crate::raw::Field<'static>, &str) {
+        (crate::raw::MESSAGE_ID, self.as_str())
+    }
+}
+
+impl AsRef<str> for MessageId {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl std::fmt::Display for MessageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for MessageId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self::new)
+    }
+}
+
+impl std::convert::From<Id128> for MessageId {
+    fn from(id: Id128) -> Self {
+        Self::new(id)
+    }
+}
+
+/// Declares one or more [[`crate::id128::MessageId`]] constants, checked at compile time.
+///
+/// ```
+/// journaled::message_id! {
+///     /// Emitted once the service is ready to accept connections.
+///     pub SERVICE_READY = "6bb4d5e81ea14a3b9a4e77d8f1b3bf0e";
+///     SERVICE_STOPPING = "f3c2a1d4-5e6f-4a8b-9c0d-1e2f3a4b5c6d";
+/// }
+///
+/// assert_eq!(SERVICE_READY.as_str(), "6bb4d5e81ea14a3b9a4e77d8f1b3bf0e");
+/// ```
+#[macro_export]
+macro_rules! message_id {
+    ($($(#[$attr:meta])* $vis:vis $name:ident = $id:literal);* $(;)?) => {
+        $(
+            $(#[$attr])*
+            $vis const $name: $crate::id128::MessageId =
+                $crate::id128::MessageId::parse_const($id);
+        )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "6bb4d5e81ea14a3b9a4e77d8f1b3bf0e";
+    const UUID: &str = "6bb4d5e8-1ea1-4a3b-9a4e-77d8f1b3bf0e";
+
+    crate::message_id! {
+        DECLARED = "6bb4d5e81ea14a3b9a4e77d8f1b3bf0e";
+    }
+
+    #[test]
+    fn test_parse() {
+        let id = Id128::parse(ID).unwrap();
+        assert_eq!(id, Id128::from_u128(0x6bb4d5e81ea14a3b9a4e77d8f1b3bf0e));
+        assert_eq!(Id128::parse(UUID), Some(id));
+        assert_eq!(Id128::parse(&ID.to_uppercase()), Some(id));
+
+        assert!(Id128::parse("").is_none());
+        assert!(Id128::parse(&ID[1..]).is_none());
+        assert!(Id128::parse("6bb4d5e81ea14a3b9a4e77d8f1b3bf0g").is_none());
+        assert!(Id128::parse("6bb4d5e8-1ea14a3b-9a4e-77d8f1b3bf0e").is_none());
+        assert!("not an id".parse::<Id128>().is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let id: Id128 = UUID.parse().unwrap();
+        assert_eq!(id.to_string(), ID);
+        assert_eq!(id.to_uuid_string(), UUID);
+    }
+
+    #[test]
+    fn test_random() {
+        let a = Id128::random().unwrap();
+        let b = Id128::random().unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.as_bytes()[6] >> 4, 4);
+        assert_eq!(a.as_bytes()[8] >> 6, 0b10);
+    }
+
+    #[test]
+    fn test_message_id() {
+        assert_eq!(DECLARED.as_str(), ID);
+        assert_eq!(DECLARED.to_string(), ID);
+        assert_eq!(DECLARED.as_value(), (crate::raw::MESSAGE_ID, ID));
+        assert_eq!(UUID.parse::<MessageId>().unwrap(), DECLARED);
+    }
+}
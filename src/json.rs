@@ -0,0 +1,538 @@
+//! Serialization and parsing of the JSON representation of entries, as produced by
+//! `journalctl -o json`.
+//!
+//! Each entry is an object with a member per field, on a line of its own.  Values are strings
+//! when they're printable UTF-8, and arrays of byte values otherwise.  A field with several values
+//! is an array of them, and a value that was too large to include is `null`.  The format is
+//! described at <https://systemd.io/JOURNAL_EXPORT_FORMATS/#journal-json-format>.
+//!
+//! ```no_run
+//! let output = std::process::Command::new("journalctl")
+//!     .args(["-o", "json", "-n", "10"])
+//!     .output()
+//!     .unwrap();
+//! for entry in journaled::json::JsonReader::new(output.stdout.as_slice()) {
+//!     let entry = entry.unwrap();
+//!     println!("{:?}", entry.get(journaled::raw::MESSAGE));
+//! }
+//! ```
+
+/// The ways in which a JSON stream can fail to parse.
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// A member name was not a valid field name.
+    InvalidField,
+    /// An address field, such as `__REALTIME_TIMESTAMP`, did not have a valid value.
+    InvalidAddress,
+    /// A member was not a string, an array of bytes, `null`, or an array of those.
+    InvalidValue,
+    /// The stream was not valid JSON, an entry was not an object, or reading from it failed.
+    Json(serde_json::Error),
+}
+
+impl std::convert::From<crate::export::FieldError> for ParseErrorKind {
+    fn from(err: crate::export::FieldError) -> Self {
+        match err {
+            crate::export::FieldError::InvalidField => ParseErrorKind::InvalidField,
+            crate::export::FieldError::InvalidAddress => ParseErrorKind::InvalidAddress,
+        }
+    }
+}
+
+/// Error returned when a JSON stream fails to parse, along with the byte offset into the stream
+/// of the entry where it occurred.
+#[derive(Debug)]
+pub struct ParseError {
+    offset: u64,
+    kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match &self.kind {
+            ParseErrorKind::InvalidField => "invalid field name",
+            ParseErrorKind::InvalidAddress => "invalid address field value",
+            ParseErrorKind::InvalidValue => "expected a string, an array of bytes, or null",
+            ParseErrorKind::Json(err) => return write!(f, "byte {}: {}", self.offset, err),
+        };
+        write!(f, "byte {}: {}", self.offset, reason)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the name of a member, preceded by a comma unless it's the first.
+fn write_name(data: &mut Vec<u8>, name: &str) {
+    if data.len() > 1 {
+        data.push(b',');
+    }
+    data.push(b'"');
+    data.extend(name.as_bytes());
+    data.extend(b"\":");
+}
+
+/// Writes a single value, or `null` if it's larger than the threshold.
+fn write_value(data: &mut Vec<u8>, value: &[u8], threshold: Option<usize>) {
+    let result = if threshold.is_some_and(|threshold| value.len() > threshold) {
+        serde_json::to_writer(&mut *data, &())
+    } else if crate::export::is_printable(value, true) {
+        let value = std::str::from_utf8(value).expect("printable values are UTF-8");
+        serde_json::to_writer(&mut *data, value)
+    } else {
+        serde_json::to_writer(&mut *data, value)
+    };
+    result.expect("writing to a Vec can't fail");
+}
+
+/// JsonWriter writes entries as JSON objects, one per line, to an underlying writer.
+///
+/// Fields that are repeated within an entry are written once, with an array of their values in
+/// the order they were given.
+#[derive(Debug)]
+pub struct JsonWriter<W> {
+    inner: W,
+    data_threshold: Option<usize>,
+}
+
+impl<W> JsonWriter<W>
+where
+    W: std::io::Write,
+{
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            data_threshold: None,
+        }
+    }
+
+    /// Writes `null` in place of values longer than `len` bytes, as `journalctl` does for values
+    /// over 4096 bytes unless it's given `--all`.
+    pub fn with_data_threshold(mut self, len: usize) -> Self {
+        self.data_threshold = Some(len);
+        self
+    }
+
+    pub fn write_entry<'a, I, V>(
+        &mut self,
+        address: &crate::export::Address,
+        values: I,
+    ) -> std::io::Result<()>
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<str>,
+    {
+        self.write_entry_bytes(
+            address,
+            values.map(|(field, value)| (field, crate::raw::StrValue(value))),
+        )
+    }
+
+    /// Writes values that aren't necessarily valid UTF-8, as arrays of bytes unless printable.
+    pub fn write_entry_bytes<'a, I, V>(
+        &mut self,
+        address: &crate::export::Address,
+        values: I,
+    ) -> std::io::Result<()>
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<[u8]>,
+    {
+        let values: Vec<_> = values.collect();
+        let mut fields: Vec<(&str, Vec<&[u8]>)> = Vec::new();
+        for (field, value) in &values {
+            match fields
+                .iter_mut()
+                .find(|(other, _)| *other == field.as_str())
+            {
+                Some((_, values)) => values.push(value.as_ref()),
+                None => fields.push((field.as_str(), vec![value.as_ref()])),
+            }
+        }
+
+        let mut data = vec![b'{'];
+        // The address isn't subject to the threshold, and its names can't collide with fields.
+        for (name, value) in address.values() {
+            write_name(&mut data, name);
+            write_value(&mut data, value.as_bytes(), None);
+        }
+        for (name, values) in fields {
+            write_name(&mut data, name);
+            match values.as_slice() {
+                [value] => write_value(&mut data, value, self.data_threshold),
+                values => {
+                    data.push(b'[');
+                    for (n, value) in values.iter().enumerate() {
+                        if n > 0 {
+                            data.push(b',');
+                        }
+                        write_value(&mut data, value, self.data_threshold);
+                    }
+                    data.push(b']');
+                }
+            }
+        }
+        data.extend(b"}\n");
+
+        self.inner.write_all(&data)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// The members of an entry's object, in the order they appear.
+struct Members(Vec<(String, serde_json::Value)>);
+
+impl<'de> serde::Deserialize<'de> for Members {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Members;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("an object of journal fields")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut members = Vec::new();
+                while let Some(member) = map.next_entry()? {
+                    members.push(member);
+                }
+                Ok(Members(members))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+impl Members {
+    fn parse(self) -> Result<crate::export::ExportEntry, ParseErrorKind> {
+        let mut parser = crate::export::EntryParser::new();
+        for (name, value) in self.0 {
+            let name = name.into_bytes();
+            match value {
+                serde_json::Value::Array(values) if !is_bytes(&values) => {
+                    for value in values {
+                        push(&mut parser, name.clone(), value)?;
+                    }
+                }
+                value => push(&mut parser, name, value)?,
+            }
+        }
+        Ok(parser.finish())
+    }
+}
+
+/// Whether an array is a single binary value rather than several values.  Empty arrays are taken
+/// to be empty values.
+fn is_bytes(values: &[serde_json::Value]) -> bool {
+    values.iter().all(serde_json::Value::is_number)
+}
+
+fn push(
+    parser: &mut crate::export::EntryParser,
+    name: Vec<u8>,
+    value: serde_json::Value,
+) -> Result<(), ParseErrorKind> {
+    match value {
+        serde_json::Value::String(value) => parser.push(name, value.into_bytes())?,
+        serde_json::Value::Array(values) => {
+            let value = values
+                .iter()
+                .map(|value| {
+                    value
+                        .as_u64()
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .ok_or(ParseErrorKind::InvalidValue)
+                })
+                .collect::<Result<_, _>>()?;
+            parser.push(name, value)?
+        }
+        serde_json::Value::Null => parser.omit(name)?,
+        _ => return Err(ParseErrorKind::InvalidValue),
+    }
+    Ok(())
+}
+
+/// Parses a single entry, such as a line of `journalctl -o json` output.
+pub fn parse(text: &str) -> Result<crate::export::ExportEntry, ParseError> {
+    let error = |kind| ParseError { offset: 0, kind };
+    serde_json::from_str::<Members>(text)
+        .map_err(|err| error(ParseErrorKind::Json(err)))?
+        .parse()
+        .map_err(error)
+}
+
+/// JsonReader reads entries from a stream of JSON objects, one at a time.
+///
+/// The objects may be separated by any whitespace, not only new lines.  The stream is buffered
+/// internally, as with [[`crate::export::ExportReader`]].  Once an error is returned, no further
+/// entries are read.
+pub struct JsonReader<R> {
+    inner: std::io::BufReader<R>,
+    offset: u64,
+    failed: bool,
+}
+
+impl<R> JsonReader<R>
+where
+    R: std::io::Read,
+{
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner: std::io::BufReader::new(inner),
+            offset: 0,
+            failed: false,
+        }
+    }
+
+    /// The number of bytes read from the stream so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Skips the whitespace before the next entry, returning whether there is one.
+    fn skip_whitespace(&mut self) -> std::io::Result<bool> {
+        use std::io::BufRead;
+
+        loop {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            let n = buf.iter().take_while(|c| c.is_ascii_whitespace()).count();
+            let more = n < buf.len();
+            self.inner.consume(n);
+            self.offset += n as u64;
+            if more {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn read_entry(&mut self) -> Result<crate::export::ExportEntry, ParseErrorKind> {
+        let mut counted = Counted {
+            inner: &mut self.inner,
+            offset: &mut self.offset,
+        };
+        let members = serde::Deserialize::deserialize(&mut serde_json::Deserializer::from_reader(
+            &mut counted,
+        ))
+        .map_err(ParseErrorKind::Json)?;
+        Members::parse(members)
+    }
+}
+
+impl<R> Iterator for JsonReader<R>
+where
+    R: std::io::Read,
+{
+    type Item = Result<crate::export::ExportEntry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let offset = self.offset;
+        let result = match self.skip_whitespace() {
+            Ok(false) => return None,
+            Ok(true) => {
+                let offset = self.offset;
+                self.read_entry()
+                    .map_err(|kind| ParseError { offset, kind })
+            }
+            Err(err) => Err(ParseError {
+                offset,
+                kind: ParseErrorKind::Json(serde_json::Error::io(err)),
+            }),
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Counted reads from a stream while counting the bytes read, since serde_json only counts them
+/// for streams of values.
+struct Counted<'a, R> {
+    inner: &'a mut R,
+    offset: &'a mut u64,
+}
+
+impl<R> std::io::Read for Counted<'_, R>
+where
+    R: std::io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        *self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R> std::fmt::Debug for JsonReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonReader")
+            .field("offset", &self.offset)
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address() -> crate::export::Address {
+        crate::export::Address::new(
+            std::time::UNIX_EPOCH + std::time::Duration::from_micros(1_700_000_000_123_456),
+        )
+    }
+
+    fn write(writer: JsonWriter<Vec<u8>>) -> String {
+        let mut writer = writer;
+        writer
+            .write_entry_bytes(
+                &address(),
+                [
+                    (crate::raw::MESSAGE, b"two\nlines".as_slice()),
+                    (crate::raw::PRIORITY, b"6"),
+                    (crate::raw::MESSAGE_ID, b"\x01\xff"),
+                    (crate::raw::PRIORITY, b"7"),
+                ]
+                .into_iter(),
+            )
+            .unwrap();
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_write_entry() {
+        assert_eq!(
+            write(JsonWriter::new(Vec::new())),
+            "{\"__REALTIME_TIMESTAMP\":\"1700000000123456\",\
+              \"MESSAGE\":\"two\\nlines\",\
+              \"PRIORITY\":[\"6\",\"7\"],\
+              \"MESSAGE_ID\":[1,255]}\n"
+        );
+        assert_eq!(
+            write(JsonWriter::new(Vec::new()).with_data_threshold(4)),
+            "{\"__REALTIME_TIMESTAMP\":\"1700000000123456\",\
+              \"MESSAGE\":null,\
+              \"PRIORITY\":[\"6\",\"7\"],\
+              \"MESSAGE_ID\":[1,255]}\n"
+        );
+    }
+
+    #[test]
+    fn test_read_round_trip() {
+        let text = write(JsonWriter::new(Vec::new()));
+        let entry = parse(&text).unwrap();
+
+        assert_eq!(entry.address(), Some(&address()));
+        assert_eq!(
+            entry.values().collect::<Vec<_>>(),
+            [
+                (crate::raw::MESSAGE, b"two\nlines".as_slice()),
+                (crate::raw::PRIORITY, b"6"),
+                (crate::raw::PRIORITY, b"7"),
+                (crate::raw::MESSAGE_ID, b"\x01\xff"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_values() {
+        let text = "{\"_PID\":\"1\",\"MESSAGE\":[[104,105],\"there\",null],\"EMPTY\":[]}\n\
+                    {\"BIG\":null}";
+        let entries = JsonReader::new(text.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].address(), None);
+        assert_eq!(
+            entries[0].protected().collect::<Vec<_>>(),
+            [("_PID", b"1".as_slice())]
+        );
+        assert_eq!(
+            entries[0].values().collect::<Vec<_>>(),
+            [
+                (crate::raw::MESSAGE, b"hi".as_slice()),
+                (crate::raw::MESSAGE, b"there"),
+                (crate::raw::Field::validate("EMPTY").unwrap(), b""),
+            ]
+        );
+        assert_eq!(
+            entries[0].get_all(crate::raw::MESSAGE).collect::<Vec<_>>(),
+            [b"hi".as_slice(), b"there"]
+        );
+        assert_eq!(entries[0].omitted().collect::<Vec<_>>(), ["MESSAGE"]);
+        assert_eq!(entries[1].omitted().collect::<Vec<_>>(), ["BIG"]);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |text: &str| {
+            let mut reader = JsonReader::new(text.as_bytes());
+            assert!(reader.next().unwrap().is_ok());
+            let error = reader.next().unwrap().unwrap_err();
+            assert!(reader.next().is_none());
+            error
+        };
+
+        let err = error("{}\n{\"lower\":\"a\"}");
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidField));
+        assert_eq!(err.offset(), 3);
+        assert_eq!(err.to_string(), "byte 3: invalid field name");
+
+        let err = error("{}\n{\"__REALTIME_TIMESTAMP\":\"now\"}");
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidAddress));
+
+        let err = error("{}\n{\"MESSAGE\":[256]}");
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidValue));
+
+        let err = error("{}\n{\"MESSAGE\":1}");
+        assert!(matches!(err.kind(), ParseErrorKind::InvalidValue));
+
+        let err = error("{}\n[]");
+        assert!(matches!(err.kind(), ParseErrorKind::Json(_)));
+
+        let err = error("{}\n{\"MESSAGE\":");
+        assert!(matches!(err.kind(), ParseErrorKind::Json(_)));
+        assert_eq!(err.offset(), 3);
+    }
+}
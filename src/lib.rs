@@ -1,8 +1,31 @@
+// Lets the code generated by journaled-derive name this crate from within it.
+extern crate self as journaled;
+
+pub mod catalog;
+pub mod dedup;
+pub mod entry;
+pub mod error;
+pub mod export;
+#[cfg(feature = "file")]
+pub mod file;
+pub mod flatten;
 mod helper;
+pub mod id128;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod location;
 #[cfg(feature = "stdlog")]
 pub mod log;
+#[cfg(feature = "file")]
+pub mod matches;
 mod memfd;
+pub mod panic;
+pub mod ratelimit;
 pub mod raw;
+#[cfg(feature = "serde")]
+pub mod serde;
 #[cfg(feature = "slog")]
 pub mod slog;
 mod socket;
+#[cfg(feature = "tracing")]
+pub mod tracing;
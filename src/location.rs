@@ -0,0 +1,178 @@
+const CLOSURE_SUFFIX: &str = "::{{closure}}";
+
+/// Location is the point in the source that an entry was sent from, as recorded by the
+/// `CODE_FILE`, `CODE_LINE`, and `CODE_FUNC` fields.
+///
+/// The [[`crate::location`]] macro captures all three, while [[`Location::caller`]] can be used
+/// from `#[track_caller]` functions, which only know the file and line.
+///
+/// ```no_run
+/// let journal = journaled::raw::JournalWriter::new().unwrap();
+/// let location = journaled::location!();
+///
+/// journal
+///     .send(
+///         [(journaled::raw::MESSAGE, "Hello World")]
+///             .into_iter()
+///             .chain(location.as_values()),
+///     )
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Location {
+    file: &'static str,
+    line: std::borrow::Cow<'static, str>,
+    func: Option<&'static str>,
+}
+
+impl Location {
+    /// Constructs a location from a line that's already formatted, such as `concat!(line!())`.
+    pub const fn new(file: &'static str, line: &'static str, func: Option<&'static str>) -> Self {
+        Self {
+            file,
+            line: std::borrow::Cow::Borrowed(line),
+            func,
+        }
+    }
+
+    /// The location of the caller, following `#[track_caller]` annotations up the stack.
+    #[track_caller]
+    pub fn caller() -> Self {
+        std::panic::Location::caller().into()
+    }
+
+    pub fn with_func(mut self, func: &'static str) -> Self {
+        self.func = Some(func);
+        self
+    }
+
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    pub fn func(&self) -> Option<&'static str> {
+        self.func
+    }
+
+    /// Is used to construct the tuples to be passed into [[`crate::raw::JournalWriter`]].
+    pub fn as_values(&self) -> impl Iterator<Item = (crate::raw::Field<'static>, &str)> + Clone {
+        [
+            Some((crate::raw::CODE_FILE, self.file)),
+            Some((crate::raw::CODE_LINE, self.line.as_ref())),
+            self.func.map(|func| (crate::raw::CODE_FUNC, func)),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl From<&'static std::panic::Location<'static>> for Location {
+    fn from(location: &'static std::panic::Location<'static>) -> Self {
+        Self {
+            file: location.file(),
+            line: location.line().to_string().into(),
+            func: None,
+        }
+    }
+}
+
+/// Recovers the enclosing function's path from the type name of an item defined inside of it.
+///
+/// This is an implementation detail of [[`crate::function`]].
+#[doc(hidden)]
+pub fn function_name(item_type_name: &'static str) -> &'static str {
+    let mut name = item_type_name
+        .rsplit_once("::")
+        .map_or(item_type_name, |(path, _)| path);
+    while let Some(stripped) = name.strip_suffix(CLOSURE_SUFFIX) {
+        name = stripped;
+    }
+    name
+}
+
+/// Expands to the path of the enclosing function, e.g. `my_crate::server::accept`.
+///
+/// Closures are attributed to the function they're defined in.
+#[macro_export]
+macro_rules! function {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            ::std::any::type_name::<T>()
+        }
+        $crate::location::function_name(type_name_of(f))
+    }};
+}
+
+/// Expands to the [[`crate::location::Location`]] of the invocation, including the enclosing
+/// function.
+#[macro_export]
+macro_rules! location {
+    () => {
+        $crate::location::Location::new(
+            ::std::file!(),
+            ::std::concat!(::std::line!()),
+            ::std::option::Option::Some($crate::function!()),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn tracked() -> Location {
+        Location::caller()
+    }
+
+    #[test]
+    fn test_location_macro() {
+        let line = line!() + 1;
+        let location = crate::location!();
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), line.to_string());
+        assert_eq!(
+            location.func(),
+            Some("journaled::location::tests::test_location_macro")
+        );
+    }
+
+    #[test]
+    fn test_function_in_closure() {
+        let closure = || crate::function!();
+        let func = closure();
+        assert_eq!(func, "journaled::location::tests::test_function_in_closure");
+    }
+
+    #[test]
+    fn test_caller() {
+        let line = line!() + 1;
+        let location = tracked();
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), line.to_string());
+        assert_eq!(location.func(), None);
+    }
+
+    #[test]
+    fn test_as_values() {
+        let location = Location::new("main.rs", "12", None);
+        assert_eq!(
+            location.as_values().collect::<Vec<_>>(),
+            [
+                (crate::raw::CODE_FILE, "main.rs"),
+                (crate::raw::CODE_LINE, "12")
+            ]
+        );
+
+        let location = location.with_func("main");
+        assert_eq!(
+            location.as_values().last(),
+            Some((crate::raw::CODE_FUNC, "main"))
+        );
+    }
+}
@@ -1,26 +1,102 @@
-fn as_priority(level: log::Level) -> crate::raw::Priority {
-    match level {
-        log::Level::Error => crate::raw::Priority::Error,
-        log::Level::Warn => crate::raw::Priority::Warning,
-        log::Level::Info => crate::raw::Priority::Info,
-        log::Level::Debug => crate::raw::Priority::Debug,
-        log::Level::Trace => crate::raw::Priority::Debug,
+impl std::convert::From<log::Level> for crate::raw::Priority {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => crate::raw::Priority::Error,
+            log::Level::Warn => crate::raw::Priority::Warning,
+            log::Level::Info => crate::raw::Priority::Info,
+            log::Level::Debug => crate::raw::Priority::Debug,
+            log::Level::Trace => crate::raw::Priority::Debug,
+        }
+    }
+}
+
+/// Maps a priority onto the closest level, with everything more severe than an error treated as
+/// [[`log::Level::Error`]].
+impl std::convert::From<crate::raw::Priority> for log::Level {
+    fn from(priority: crate::raw::Priority) -> Self {
+        match priority {
+            crate::raw::Priority::Emergency
+            | crate::raw::Priority::Alert
+            | crate::raw::Priority::Critical
+            | crate::raw::Priority::Error => log::Level::Error,
+            crate::raw::Priority::Warning => log::Level::Warn,
+            crate::raw::Priority::Notice | crate::raw::Priority::Info => log::Level::Info,
+            crate::raw::Priority::Debug => log::Level::Debug,
+        }
     }
 }
 
 const TARGET: crate::raw::Field = crate::raw::Field::unchecked("TARGET");
 const MODULE_PATH: crate::raw::Field = crate::raw::Field::unchecked("MODULE_PATH");
+const LOG_LEVEL: crate::raw::Field = crate::raw::Field::unchecked("LOG_LEVEL");
+#[cfg(feature = "stdlog-kv")]
+const KV_PREFIX: crate::raw::Field = crate::raw::Field::unchecked("KV_");
 
-impl log::Log for crate::raw::JournalWriter {
-    fn enabled(&self, _metadata: &log::Metadata) -> bool {
-        true
+/// Fields written for every record, which key-values are not allowed to replace.
+#[cfg(feature = "stdlog-kv")]
+const RESERVED: [crate::raw::Field; 7] = [
+    crate::raw::PRIORITY,
+    crate::raw::MESSAGE,
+    LOG_LEVEL,
+    TARGET,
+    crate::raw::CODE_FILE,
+    crate::raw::CODE_LINE,
+    MODULE_PATH,
+];
+
+/// PriorityMap decides the priority that records of each level are sent with, or whether they're
+/// sent at all.
+///
+/// By default levels are mapped as by `From<log::Level>`, which sends both `Debug` and `Trace`
+/// as [[`crate::raw::Priority::Debug`]].  The level is always written to the `LOG_LEVEL` field,
+/// so the two can still be told apart.
+///
+/// ```
+/// use journaled::raw::Priority;
+///
+/// let priorities = journaled::log::PriorityMap::default()
+///     .map(log::Level::Info, Priority::Notice)
+///     .discard(log::Level::Trace);
+/// assert_eq!(priorities.get(log::Level::Info), Some(Priority::Notice));
+/// assert_eq!(priorities.get(log::Level::Trace), None);
+/// ```
+pub type PriorityMap = crate::raw::PriorityMap<log::Level>;
+
+impl crate::raw::Level for log::Level {
+    const ALL: &'static [Self] = &[
+        log::Level::Error,
+        log::Level::Warn,
+        log::Level::Info,
+        log::Level::Debug,
+        log::Level::Trace,
+    ];
+
+    fn index(self) -> usize {
+        // Levels start from Error at 1.
+        self as usize - 1
     }
+}
 
-    fn log(&self, record: &log::Record) {
-        if !self.enabled(record.metadata()) {
-            return;
-        }
+/// Format controls how a record is turned into fields.
+#[derive(Clone, Debug, Default)]
+struct Format {
+    priorities: PriorityMap,
+    #[cfg(feature = "stdlog-kv")]
+    key_prefix: Option<crate::raw::OwnedField>,
+    #[cfg(feature = "stdlog-kv")]
+    flatten_keys: Option<crate::flatten::Collision>,
+}
+
+impl Format {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.priorities.get(metadata.level()).is_some()
+    }
 
+    fn send(&self, writer: &crate::raw::JournalWriter, record: &log::Record) {
+        let priority = match self.priorities.get(record.level()) {
+            Some(priority) => priority,
+            None => return,
+        };
         let line = record.line().as_ref().map(ToString::to_string);
         let msg = record.args().as_str().map_or_else(
             || record.args().to_string().into(),
@@ -28,32 +104,686 @@ impl log::Log for crate::raw::JournalWriter {
         );
 
         let values = [
-            as_priority(record.level()).as_value(),
+            priority.as_value(),
             (crate::raw::MESSAGE, &msg),
+            (LOG_LEVEL, record.level().as_str()),
             (TARGET, record.target()),
         ];
+        // log doesn't record the enclosing function, so CODE_FUNC can't be set.  The module path
+        // is included instead to narrow down where the record came from.
         let opt_values = [
             record.file().map(|x| (crate::raw::CODE_FILE, x)),
             line.as_ref().map(|x| (crate::raw::CODE_LINE, x.as_str())),
             record.module_path().map(|x| (MODULE_PATH, x)),
         ];
+        let key_values = self.key_values(record);
 
-        if let Err(err) = self.send(values.into_iter().chain(opt_values.into_iter().flatten())) {
+        if let Err(err) = writer.send(
+            values
+                .into_iter()
+                .chain(opt_values.into_iter().flatten())
+                .chain(
+                    key_values
+                        .iter()
+                        .filter(crate::error::first_errno())
+                        .map(|(k, v)| (k.into(), v.as_str())),
+                ),
+        ) {
             eprintln!("logging failed: {}", err);
         }
     }
 
-    fn flush(&self) {}
+    #[cfg(not(feature = "stdlog-kv"))]
+    fn key_values(&self, _record: &log::Record) -> Vec<(crate::raw::OwnedField, String)> {
+        Vec::new()
+    }
+
+    /// Converts the key-values of a record into fields.
+    ///
+    /// Keys are sanitized by [[`crate::raw::OwnedField::sanitize`]], or prefixed by
+    /// [[`crate::raw::OwnedField::sanitize_with_prefix`]] when a prefix is configured.  Keys that
+    /// would replace a field written for every record, such as `MESSAGE` or `PRIORITY`, are
+    /// prefixed with `KV_` instead, e.g. `message` is written as `KV_MESSAGE`.  Errors are written
+    /// as described in [[`crate::error`]].
+    ///
+    /// When keys are flattened, they're named by [[`crate::flatten::FieldNames`]] instead, so
+    /// `http.status` is written as `HTTP__STATUS`.  The fields written for every record are
+    /// reserved, so keys that share a name with one of them or with another key are renamed or
+    /// dropped, e.g. `message` is written as `MESSAGE_2`.
+    #[cfg(feature = "stdlog-kv")]
+    fn key_values(&self, record: &log::Record) -> Vec<(crate::raw::OwnedField, String)> {
+        let names = self.flatten_keys.map(|collision| {
+            let names = crate::flatten::FieldNames::new(collision);
+            let mut names = match &self.key_prefix {
+                Some(prefix) => names.with_prefix(prefix.into()),
+                None => names,
+            };
+            for field in RESERVED {
+                names.reserve(field);
+            }
+            names
+        });
+        let mut visitor = KeyValues {
+            prefix: self.key_prefix.as_ref(),
+            names,
+            fields: Vec::new(),
+        };
+        // Collecting the key-values never fails, only the source may return an error.
+        let _ = record.key_values().visit(&mut visitor);
+        visitor.fields
+    }
+}
+
+#[cfg(feature = "stdlog-kv")]
+struct KeyValues<'a> {
+    prefix: Option<&'a crate::raw::OwnedField>,
+    names: Option<crate::flatten::FieldNames>,
+    fields: Vec<(crate::raw::OwnedField, String)>,
+}
+
+#[cfg(feature = "stdlog-kv")]
+impl KeyValues<'_> {
+    fn push(&mut self, key: &str, value: String) {
+        let field = match (&mut self.names, self.prefix) {
+            (Some(names), _) => names.name(key),
+            (None, Some(prefix)) => Some(crate::raw::OwnedField::sanitize_with_prefix(
+                key,
+                prefix.into(),
+            )),
+            (None, None) => crate::raw::OwnedField::sanitize(key).map(|field| {
+                if RESERVED.contains(&(&field).into()) {
+                    crate::raw::OwnedField::sanitize_with_prefix(field.as_str(), KV_PREFIX)
+                } else {
+                    field
+                }
+            }),
+        };
+
+        if let Some(field) = field {
+            self.fields.push((field, value));
+        }
+    }
+
+    fn push_error(&mut self, key: &str, error: &(dyn std::error::Error + 'static)) {
+        for (part, value) in crate::error::parts(error) {
+            match part.key(key) {
+                Some(key) => self.push(&key, value),
+                None => self.fields.push((crate::raw::ERRNO.into(), value)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "stdlog-kv")]
+impl<'kvs> log::kv::VisitSource<'kvs> for KeyValues<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        match value.to_borrowed_error() {
+            Some(error) => self.push_error(key.as_str(), error),
+            None => self.push(key.as_str(), value.to_string()),
+        }
+        Ok(())
+    }
+}
+
+impl log::Log for crate::raw::JournalWriter {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        Format::default().send(self, record);
+    }
+
+    fn flush(&self) {
+        if let Err(err) = crate::raw::JournalWriter::flush(self) {
+            eprintln!("logging failed: {}", err);
+        }
+    }
+}
+
+/// Filter decides which records are logged, based on their level and target.
+///
+/// Directives apply to a target and all of the modules beneath it, with the most specific
+/// directive taking precedence.  Targets that don't match any directive use the default level.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Filter {
+    default: log::LevelFilter,
+    directives: Vec<(String, log::LevelFilter)>,
+}
+
+impl Filter {
+    pub fn new(default: log::LevelFilter) -> Self {
+        Self {
+            default,
+            directives: Vec::new(),
+        }
+    }
+
+    /// Parses `RUST_LOG` style directives, e.g. `warn,my_app=debug,my_app::db=trace`.
+    ///
+    /// Each comma separated directive is either a level, which sets the default, a target, which
+    /// enables everything for it, or `target=level`.  The default level is `info` unless set, as
+    /// for [[`Filter::default`]].
+    pub fn parse(directives: &str) -> Result<Self, ParseFilterError> {
+        let mut filter = Self::default();
+        filter.apply(directives)?;
+        Ok(filter)
+    }
+
+    /// Adds the parsed directives to the filter, replacing any that have the same target.
+    fn apply(&mut self, directives: &str) -> Result<(), ParseFilterError> {
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let error = || ParseFilterError {
+                directive: directive.to_owned(),
+            };
+
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    let level = level.trim().parse().map_err(|_| error())?;
+                    if target.is_empty() {
+                        return Err(error());
+                    }
+                    self.set_level(target, level);
+                }
+                None => match directive.parse() {
+                    Ok(level) => self.default = level,
+                    Err(_) if is_valid_target(directive) => {
+                        self.set_level(directive, log::LevelFilter::Trace)
+                    }
+                    Err(_) => return Err(error()),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    pub fn default_level(&self) -> log::LevelFilter {
+        self.default
+    }
+
+    pub fn set_default_level(&mut self, level: log::LevelFilter) {
+        self.default = level;
+    }
+
+    /// Sets the level for a target and the modules beneath it, e.g. `my_app::db`.
+    pub fn set_level<S>(&mut self, target: S, level: log::LevelFilter)
+    where
+        S: Into<String>,
+    {
+        let target = target.into();
+        match self.directives.iter_mut().find(|(t, _)| *t == target) {
+            Some(directive) => directive.1 = level,
+            None => self.directives.push((target, level)),
+        }
+    }
+
+    /// The level that applies to a target.
+    pub fn level(&self, target: &str) -> log::LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(t, _)| {
+                target
+                    .strip_prefix(t.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(t, _)| t.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    pub fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level(metadata.target())
+    }
+
+    /// The most verbose level of any target, which is passed to [[`log::set_max_level`]] so that
+    /// disabled records are skipped before reaching the logger.
+    pub fn max_level(&self) -> log::LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(log::LevelFilter::Info)
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn is_valid_target(target: &str) -> bool {
+    target
+        .split("::")
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
+/// Error returned when a filter directive can't be parsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseFilterError {
+    directive: String,
+}
+
+impl std::fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid log filter directive: {:?}", self.directive)
+    }
+}
+
+impl std::error::Error for ParseFilterError {}
+
+/// The most verbose level that's sent, which is passed to [[`log::set_max_level`]]: the filter's
+/// [[`Filter::max_level`]], unless the priorities discard the levels above some level.
+fn max_level(filter: &Filter, priorities: &PriorityMap) -> log::LevelFilter {
+    let mapped = <log::Level as crate::raw::Level>::ALL
+        .iter()
+        .filter(|level| priorities.get(**level).is_some())
+        .map(|level| level.to_level_filter())
+        .max()
+        .unwrap_or(log::LevelFilter::Off);
+    filter.max_level().min(mapped)
+}
+
+/// Logger is a [[`log::Log`]] implementation that sends records to the journal, filtering them by
+/// level and target.
+#[derive(Debug)]
+pub struct Logger {
+    writer: crate::raw::JournalWriter,
+    format: Format,
+    filter: std::sync::Arc<std::sync::RwLock<Filter>>,
 }
 
+impl Logger {
+    /// A handle that can change the filter after the logger has been installed.
+    pub fn handle(&self) -> Handle {
+        Handle {
+            filter: self.filter.clone(),
+            priorities: self.format.priorities,
+        }
+    }
+
+    fn max_level(&self) -> log::LevelFilter {
+        max_level(&self.filter(), &self.format.priorities)
+    }
+
+    fn filter(&self) -> std::sync::RwLockReadGuard<'_, Filter> {
+        self.filter.read().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.format.enabled(metadata) && self.filter().enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        self.format.send(&self.writer, record);
+    }
+
+    fn flush(&self) {
+        log::Log::flush(&self.writer);
+    }
+}
+
+/// Handle changes the levels of an installed [[`Logger`]] at runtime.
+#[derive(Clone, Debug)]
+pub struct Handle {
+    filter: std::sync::Arc<std::sync::RwLock<Filter>>,
+    priorities: PriorityMap,
+}
+
+impl Handle {
+    pub fn filter(&self) -> Filter {
+        self.filter
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    pub fn set_default_level(&self, level: log::LevelFilter) {
+        self.update(|filter| filter.set_default_level(level));
+    }
+
+    pub fn set_level<S>(&self, target: S, level: log::LevelFilter)
+    where
+        S: Into<String>,
+    {
+        self.update(|filter| filter.set_level(target, level));
+    }
+
+    /// Replaces the filter entirely, e.g. with one parsed from a reloaded configuration.
+    pub fn set_filter(&self, filter: Filter) {
+        self.update(|current| *current = filter);
+    }
+
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Filter),
+    {
+        let mut filter = self.filter.write().unwrap_or_else(|err| err.into_inner());
+        f(&mut filter);
+        log::set_max_level(max_level(&filter, &self.priorities));
+    }
+}
+
+/// Builder configures the [[`Logger`]] installed by [[`init_with`]].
+#[derive(Debug, Default)]
+pub struct Builder {
+    writer: Option<crate::raw::JournalWriter>,
+    format: Format,
+    filter: Filter,
+}
+
+impl Builder {
+    /// A builder that logs `info` and above for every target.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder configured by the `RUST_LOG` environment variable, if it's set.
+    pub fn from_env() -> Result<Self, ParseFilterError> {
+        match std::env::var("RUST_LOG") {
+            Ok(directives) => Self::new().parse_filters(&directives),
+            Err(_) => Ok(Self::new()),
+        }
+    }
+
+    /// Uses an already configured writer, e.g. one with a facility, instead of a default one.
+    pub fn writer(mut self, writer: crate::raw::JournalWriter) -> Self {
+        self.writer = Some(writer);
+        self
+    }
+
+    pub fn default_level(mut self, level: log::LevelFilter) -> Self {
+        self.filter.set_default_level(level);
+        self
+    }
+
+    /// Sets the level for a target and the modules beneath it.
+    pub fn filter<S>(mut self, target: S, level: log::LevelFilter) -> Self
+    where
+        S: Into<String>,
+    {
+        self.filter.set_level(target, level);
+        self
+    }
+
+    /// Prefixes the field names of a record's key-values, e.g. with a prefix of `APP_` the key
+    /// `user_id` is written as `APP_USER_ID`.
+    #[cfg(feature = "stdlog-kv")]
+    pub fn key_prefix(mut self, prefix: crate::raw::Field) -> Self {
+        self.format.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Flattens nested keys as described in [[`crate::flatten`]], e.g. `http.status` is written
+    /// as `HTTP__STATUS`, and resolves keys that share a name within a record by `collision`.
+    #[cfg(feature = "stdlog-kv")]
+    pub fn flatten_keys(mut self, collision: crate::flatten::Collision) -> Self {
+        self.format.flatten_keys = Some(collision);
+        self
+    }
+
+    /// Changes the priority that each level is sent with, e.g. to send `Info` as `Notice`.
+    pub fn priorities(mut self, priorities: PriorityMap) -> Self {
+        self.format.priorities = priorities;
+        self
+    }
+
+    /// Adds `RUST_LOG` style directives, as described by [[`Filter::parse`]].
+    pub fn parse_filters(mut self, directives: &str) -> Result<Self, ParseFilterError> {
+        self.filter.apply(directives)?;
+        Ok(self)
+    }
+
+    pub fn build(self) -> std::io::Result<Logger> {
+        let writer = match self.writer {
+            Some(writer) => writer,
+            None => crate::raw::JournalWriter::new()?,
+        };
+        Ok(Logger {
+            writer,
+            format: self.format,
+            filter: std::sync::Arc::new(std::sync::RwLock::new(self.filter)),
+        })
+    }
+}
+
+/// Installs a logger that sends `info` and above to the journal.
 pub fn init() -> Result<(), Box<dyn std::error::Error>> {
-    static LOGGER: once_cell::sync::OnceCell<crate::raw::JournalWriter> =
-        once_cell::sync::OnceCell::new();
+    init_with(Builder::new()).map(|_| ())
+}
+
+/// Installs a logger configured by the builder, returning a handle to change its levels.
+pub fn init_with(builder: Builder) -> Result<Handle, Box<dyn std::error::Error>> {
+    let logger = builder.build()?;
+    logger.writer.check()?;
+    let handle = logger.handle();
+    let max_level = logger.max_level();
+    log::set_boxed_logger(Box::new(logger))?;
+    log::set_max_level(max_level);
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_parse() {
+        let filter = Filter::parse("warn,my_app=debug, my_app::db = trace,other").unwrap();
+        assert_eq!(filter.default_level(), log::LevelFilter::Warn);
+        assert_eq!(filter.level("my_app"), log::LevelFilter::Debug);
+        assert_eq!(filter.level("my_app::server"), log::LevelFilter::Debug);
+        assert_eq!(filter.level("my_app::db::pool"), log::LevelFilter::Trace);
+        assert_eq!(filter.level("my_application"), log::LevelFilter::Warn);
+        assert_eq!(filter.level("other"), log::LevelFilter::Trace);
+        assert_eq!(filter.max_level(), log::LevelFilter::Trace);
+
+        assert_eq!(
+            Filter::parse("").unwrap().default_level(),
+            log::LevelFilter::Info
+        );
+        assert_eq!(Filter::parse("").unwrap(), Filter::default());
+        assert_eq!(
+            Filter::parse("my_app=off").unwrap().level("my_app"),
+            log::LevelFilter::Off
+        );
+    }
+
+    #[test]
+    fn test_filter_parse_errors() {
+        assert!(Filter::parse("my_app=loud").is_err());
+        assert!(Filter::parse("=debug").is_err());
+        assert!(Filter::parse("my app").is_err());
+        assert!(Filter::parse("my_app/regex").is_err());
+    }
+
+    #[cfg(feature = "stdlog-kv")]
+    #[test]
+    fn test_key_values() {
+        let kvs = [
+            ("user_id", 42),
+            ("http.status", 200),
+            ("message", 1),
+            ("_", 0),
+        ];
+        let record = log::Record::builder()
+            .args(format_args!("Hello"))
+            .key_values(&kvs)
+            .build();
+        let fields = |format: &Format| {
+            format
+                .key_values(&record)
+                .into_iter()
+                .map(|(k, v)| (k.as_str().to_owned(), v))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            fields(&Format::default()),
+            [
+                ("USER_ID".to_owned(), "42".to_owned()),
+                ("HTTP_STATUS".to_owned(), "200".to_owned()),
+                ("KV_MESSAGE".to_owned(), "1".to_owned()),
+            ]
+        );
+
+        let prefixed = Format {
+            key_prefix: Some(crate::raw::Field::validate("APP_").unwrap().into()),
+            ..Format::default()
+        };
+        assert_eq!(
+            fields(&prefixed),
+            [
+                ("APP_USER_ID".to_owned(), "42".to_owned()),
+                ("APP_HTTP_STATUS".to_owned(), "200".to_owned()),
+                ("APP_MESSAGE".to_owned(), "1".to_owned()),
+                ("APP__".to_owned(), "0".to_owned()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "stdlog-kv")]
+    #[test]
+    fn test_key_values_flattened() {
+        let kvs = [
+            ("http.status", 200),
+            ("http_status", 201),
+            ("http/status", 202),
+            ("message", 1),
+            ("kv.message", 2),
+        ];
+        let record = log::Record::builder()
+            .args(format_args!("Hello"))
+            .key_values(&kvs)
+            .build();
+        let format = Format {
+            flatten_keys: Some(crate::flatten::Collision::Suffix),
+            ..Format::default()
+        };
+        let fields: Vec<_> = format
+            .key_values(&record)
+            .into_iter()
+            .map(|(k, v)| (k.as_str().to_owned(), v))
+            .collect();
+
+        assert_eq!(
+            fields,
+            [
+                ("HTTP__STATUS".to_owned(), "200".to_owned()),
+                ("HTTP_STATUS".to_owned(), "201".to_owned()),
+                ("HTTP__STATUS_2".to_owned(), "202".to_owned()),
+                ("MESSAGE_2".to_owned(), "1".to_owned()),
+                ("KV__MESSAGE".to_owned(), "2".to_owned()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "stdlog-kv")]
+    #[test]
+    fn test_key_values_error() {
+        let error = std::io::Error::from_raw_os_error(2);
+        let kvs = [
+            ("error", log::kv::Value::from_dyn_error(&error)),
+            ("cause", log::kv::Value::from_dyn_error(&error)),
+        ];
+        let record = log::Record::builder()
+            .args(format_args!("Hello"))
+            .key_values(&kvs)
+            .build();
+        let fields = |format: &Format| {
+            let key_values = format.key_values(&record);
+            key_values
+                .iter()
+                .filter(crate::error::first_errno())
+                .map(|(k, _)| k.as_str().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(fields(&Format::default()), ["ERROR", "ERRNO", "CAUSE"]);
 
-    let logger = LOGGER.get_or_try_init(crate::raw::JournalWriter::new)?;
-    logger.check()?;
-    log::set_logger(logger)?;
-    log::set_max_level(log::LevelFilter::Info);
+        // ERRNO is written as is, never prefixed or renamed, and only for the first error.
+        let flattened = Format {
+            key_prefix: Some(crate::raw::Field::validate("APP_").unwrap().into()),
+            flatten_keys: Some(crate::flatten::Collision::Suffix),
+            ..Format::default()
+        };
+        assert_eq!(fields(&flattened), ["APP_ERROR", "ERRNO", "APP_CAUSE"]);
+    }
+
+    #[test]
+    fn test_priority_map() {
+        let priorities = PriorityMap::default();
+        assert_eq!(
+            priorities.get(log::Level::Error),
+            Some(crate::raw::Priority::Error)
+        );
+        assert_eq!(
+            priorities.get(log::Level::Trace),
+            Some(crate::raw::Priority::Debug)
+        );
+
+        let format = Builder::new()
+            .priorities(priorities.discard(log::Level::Debug))
+            .format;
+        let metadata = |level| log::Metadata::builder().level(level).build();
+        assert!(format.enabled(&metadata(log::Level::Trace)));
+        assert!(!format.enabled(&metadata(log::Level::Debug)));
+    }
 
-    Ok(())
+    #[test]
+    fn test_max_level() {
+        let mut filter = Filter::default();
+        filter.set_level("verbose", log::LevelFilter::Trace);
+        let priorities = PriorityMap::default();
+        assert_eq!(max_level(&filter, &priorities), log::LevelFilter::Trace);
+
+        let priorities = priorities.discard(log::Level::Trace);
+        assert_eq!(max_level(&filter, &priorities), log::LevelFilter::Debug);
+        filter.set_level("verbose", log::LevelFilter::Warn);
+        assert_eq!(max_level(&filter, &priorities), log::LevelFilter::Info);
+
+        let all = <log::Level as crate::raw::Level>::ALL;
+        let none = all
+            .iter()
+            .fold(priorities, |priorities, level| priorities.discard(*level));
+        assert_eq!(max_level(&filter, &none), log::LevelFilter::Off);
+    }
+
+    #[test]
+    fn test_filter_enabled() {
+        let mut filter = Filter::default();
+        filter.set_level("noisy", log::LevelFilter::Error);
+
+        let metadata = |target, level| log::Metadata::builder().target(target).level(level).build();
+        assert!(filter.enabled(&metadata("app", log::Level::Info)));
+        assert!(!filter.enabled(&metadata("app", log::Level::Debug)));
+        assert!(!filter.enabled(&metadata("noisy::inner", log::Level::Warn)));
+        assert!(filter.enabled(&metadata("noisy::inner", log::Level::Error)));
+        assert_eq!(filter.max_level(), log::LevelFilter::Info);
+    }
 }
@@ -0,0 +1,389 @@
+//! Matches select the entries read from journal files by the values of their fields, in the same
+//! way as `sd_journal_add_match` and the matches given to `journalctl`.
+//!
+//! Matches are added to a term.  An entry matches a term when it has one of the values given for
+//! each field in the term, so matches on the same field are alternatives, and matches on different
+//! fields must all hold.  A disjunction starts a new term, which entries may match instead, and a
+//! conjunction starts a new group of terms, which entries must also match.  A disjunction or
+//! conjunction with nothing before it has no effect.
+//!
+//! Fields that journald adds itself start with an underscore, so they're named by
+//! [[`ProtectedField`]] rather than [[`crate::raw::Field`]], e.g. [[`SYSTEMD_UNIT`]].
+//!
+//! ```no_run
+//! let mut matches = journaled::matches::Matches::new();
+//! // (SYSLOG_IDENTIFIER=sshd AND (PRIORITY=2 OR PRIORITY=3))
+//! // OR (SYSLOG_IDENTIFIER=sudo AND _SYSTEMD_UNIT=sudo.service)
+//! matches
+//!     .add_match(journaled::raw::SYSLOG_IDENTIFIER, "sshd")
+//!     .add_match(journaled::raw::PRIORITY, "2")
+//!     .add_match(journaled::raw::PRIORITY, "3")
+//!     .add_disjunction()
+//!     .add_match(journaled::raw::SYSLOG_IDENTIFIER, "sudo")
+//!     .add_protected_match(journaled::matches::SYSTEMD_UNIT, "sudo.service");
+//!
+//! let path = "/var/log/journal/00000000000000000000000000000000/system.journal";
+//! let mut reader = journaled::file::JournalReader::open(path).unwrap();
+//! reader.set_matches(&matches).unwrap();
+//! for entry in reader {
+//!     println!("{:?}", entry.unwrap().get(journaled::raw::MESSAGE));
+//! }
+//! ```
+
+pub const PID: ProtectedField = ProtectedField::unchecked("_PID");
+pub const UID: ProtectedField = ProtectedField::unchecked("_UID");
+pub const GID: ProtectedField = ProtectedField::unchecked("_GID");
+pub const COMM: ProtectedField = ProtectedField::unchecked("_COMM");
+pub const EXE: ProtectedField = ProtectedField::unchecked("_EXE");
+pub const CMDLINE: ProtectedField = ProtectedField::unchecked("_CMDLINE");
+pub const TRANSPORT: ProtectedField = ProtectedField::unchecked("_TRANSPORT");
+pub const HOSTNAME: ProtectedField = ProtectedField::unchecked("_HOSTNAME");
+pub const BOOT_ID: ProtectedField = ProtectedField::unchecked("_BOOT_ID");
+pub const MACHINE_ID: ProtectedField = ProtectedField::unchecked("_MACHINE_ID");
+pub const SYSTEMD_CGROUP: ProtectedField = ProtectedField::unchecked("_SYSTEMD_CGROUP");
+pub const SYSTEMD_SLICE: ProtectedField = ProtectedField::unchecked("_SYSTEMD_SLICE");
+pub const SYSTEMD_UNIT: ProtectedField = ProtectedField::unchecked("_SYSTEMD_UNIT");
+pub const SYSTEMD_USER_UNIT: ProtectedField = ProtectedField::unchecked("_SYSTEMD_USER_UNIT");
+pub const SYSTEMD_INVOCATION_ID: ProtectedField =
+    ProtectedField::unchecked("_SYSTEMD_INVOCATION_ID");
+
+/// ProtectedField is the name of a field that journald adds to entries itself, which starts with
+/// an underscore so that clients can't send it.  Other than the underscore, the name follows the
+/// same rules as a [[`crate::raw::Field`]].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ProtectedField<'a> {
+    inner: &'a str,
+}
+
+impl<'a> ProtectedField<'a> {
+    /// Returns the field if it's an underscore followed by a valid [[`crate::raw::Field`]].
+    pub fn validate(inner: &'a str) -> Option<Self> {
+        let valid = inner.len() <= crate::raw::FIELD_LEN_MAX
+            && inner
+                .strip_prefix('_')
+                .and_then(crate::raw::Field::validate)
+                .is_some();
+        valid.then_some(Self { inner })
+    }
+
+    /// Allows for the construction of known valid fields at compile time, as
+    /// [[`crate::raw::Field::unchecked`]] does.
+    pub const fn unchecked(inner: &'a str) -> Self {
+        Self { inner }
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.inner
+    }
+}
+
+/// Error returned when an argument given to [[`Matches::parse`]] isn't a match.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    arg: String,
+}
+
+impl ParseError {
+    /// The argument that couldn't be parsed.
+    pub fn arg(&self) -> &str {
+        &self.arg
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is not a match, expected `FIELD=value` or `+`",
+            self.arg
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The matches of a term, as field names and values.
+type Term = Vec<(String, Vec<u8>)>;
+
+/// Matches is a set of matches, which are resolved against a file by
+/// [[`crate::file::JournalReader::set_matches`]].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Matches {
+    /// Groups that entries must all match, each of which is matched by any of its terms.
+    groups: Vec<Vec<Term>>,
+}
+
+impl Matches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses matches as given to `journalctl`, where each argument is either `FIELD=value`, or
+    /// `+` for a disjunction.  Fields may start with underscores, so that fields added by journald,
+    /// such as `_SYSTEMD_UNIT`, can be matched.
+    pub fn parse<I, S>(args: I) -> Result<Self, ParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut matches = Self::new();
+        for arg in args {
+            match arg.as_ref() {
+                "+" => {
+                    matches.add_disjunction();
+                }
+                arg => {
+                    let (name, value) = arg
+                        .split_once('=')
+                        .filter(|(name, _)| crate::export::is_valid_name(name.as_bytes()))
+                        .ok_or_else(|| ParseError {
+                            arg: arg.to_owned(),
+                        })?;
+                    matches.push(name.to_owned(), value.as_bytes().to_vec());
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Adds a match to the current term.
+    pub fn add_match<F, V>(&mut self, field: F, value: V) -> &mut Self
+    where
+        F: Into<crate::raw::OwnedField>,
+        V: Into<Vec<u8>>,
+    {
+        self.push(field.into().as_str().to_owned(), value.into());
+        self
+    }
+
+    /// Adds a match on a field that journald adds itself to the current term.
+    pub fn add_protected_match<V>(&mut self, field: ProtectedField, value: V) -> &mut Self
+    where
+        V: Into<Vec<u8>>,
+    {
+        self.push(field.as_str().to_owned(), value.into());
+        self
+    }
+
+    /// Starts a new term, so that entries can match either the terms before or the one after.
+    pub fn add_disjunction(&mut self) -> &mut Self {
+        if let Some(group) = self.groups.last_mut() {
+            if group.last().is_some_and(|term| !term.is_empty()) {
+                group.push(Term::new());
+            }
+        }
+        self
+    }
+
+    /// Starts a new group of terms, so that entries must match both the terms before and those
+    /// after.
+    pub fn add_conjunction(&mut self) -> &mut Self {
+        if self
+            .groups
+            .last()
+            .is_some_and(|group| group.iter().any(|term| !term.is_empty()))
+        {
+            self.groups.push(Vec::new());
+        }
+        self
+    }
+
+    /// Whether there are no matches, in which case every entry is read.
+    pub fn is_empty(&self) -> bool {
+        self.groups.iter().flatten().all(Vec::is_empty)
+    }
+
+    fn push(&mut self, name: String, value: Vec<u8>) {
+        if self.groups.last().map_or(true, Vec::is_empty) {
+            self.groups.push(vec![Term::new()]);
+        }
+        let group = self.groups.last_mut().expect("a group was added");
+        let term = group.last_mut().expect("groups have a term");
+        term.push((name, value));
+    }
+
+    /// Resolves the matches into the sorted offsets of the entries that match, given the sorted
+    /// offsets of the entries with each `FIELD=value` pair.  Returns `None` if there are no
+    /// matches.
+    pub(crate) fn resolve<F, E>(&self, mut lookup: F) -> Result<Option<Vec<u64>>, E>
+    where
+        F: FnMut(&str, &[u8]) -> Result<Vec<u64>, E>,
+    {
+        let mut all: Option<Vec<u64>> = None;
+        for group in &self.groups {
+            let mut any: Option<Vec<u64>> = None;
+            for term in group.iter().filter(|term| !term.is_empty()) {
+                let mut fields: Vec<&str> = Vec::new();
+                for (name, _) in term {
+                    if !fields.contains(&name.as_str()) {
+                        fields.push(name);
+                    }
+                }
+
+                let mut term_entries: Option<Vec<u64>> = None;
+                for field in fields {
+                    let mut field_entries = Vec::new();
+                    for (_, value) in term.iter().filter(|(name, _)| name == field) {
+                        field_entries = union(field_entries, lookup(field, value)?);
+                    }
+                    term_entries = Some(match term_entries {
+                        Some(entries) => intersection(entries, &field_entries),
+                        None => field_entries,
+                    });
+                }
+                let term_entries = term_entries.expect("terms aren't empty");
+                any = Some(match any {
+                    Some(entries) => union(entries, term_entries),
+                    None => term_entries,
+                });
+            }
+
+            if let Some(any) = any {
+                all = Some(match all {
+                    Some(entries) => intersection(entries, &any),
+                    None => any,
+                });
+            }
+        }
+        Ok(all)
+    }
+}
+
+fn union(mut a: Vec<u64>, b: Vec<u64>) -> Vec<u64> {
+    a.extend(b);
+    a.sort_unstable();
+    a.dedup();
+    a
+}
+
+fn intersection(mut a: Vec<u64>, b: &[u64]) -> Vec<u64> {
+    a.retain(|offset| b.binary_search(offset).is_ok());
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: crate::raw::Field = crate::raw::UNIT;
+    const PRIORITY: crate::raw::Field = crate::raw::PRIORITY;
+
+    /// Entries 1 through 9, where the unit is `a` for odd entries, and `b` for even ones, and
+    /// the priority is the remainder of dividing by 3.
+    fn lookup(field: &str, value: &[u8]) -> Result<Vec<u64>, ()> {
+        let value = std::str::from_utf8(value).unwrap();
+        Ok((1..10)
+            .filter(|n| match field {
+                "UNIT" => value == if n % 2 == 1 { "a" } else { "b" },
+                "PRIORITY" => value == (n % 3).to_string(),
+                _ => false,
+            })
+            .collect())
+    }
+
+    fn resolve(matches: &Matches) -> Option<Vec<u64>> {
+        matches.resolve(lookup).unwrap()
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut matches = Matches::new();
+        assert!(matches.is_empty());
+        assert_eq!(resolve(&matches), None);
+
+        matches.add_disjunction().add_conjunction();
+        assert!(matches.is_empty());
+        assert_eq!(resolve(&matches), None);
+    }
+
+    #[test]
+    fn test_term() {
+        let mut matches = Matches::new();
+        matches.add_match(UNIT, "a");
+        assert_eq!(resolve(&matches), Some(vec![1, 3, 5, 7, 9]));
+
+        // The same field is an alternative, and other fields must also match.
+        matches.add_match(UNIT, "b");
+        assert_eq!(resolve(&matches), Some((1..10).collect()));
+        matches.add_match(PRIORITY, "0");
+        assert_eq!(resolve(&matches), Some(vec![3, 6, 9]));
+
+        let mut missing = Matches::new();
+        missing.add_match(crate::raw::MESSAGE, "x");
+        assert_eq!(resolve(&missing), Some(vec![]));
+    }
+
+    #[test]
+    fn test_disjunction_conjunction() {
+        // (UNIT=a AND PRIORITY=0) OR PRIORITY=2
+        let mut matches = Matches::new();
+        matches
+            .add_match(UNIT, "a")
+            .add_match(PRIORITY, "0")
+            .add_disjunction()
+            .add_disjunction()
+            .add_match(PRIORITY, "2");
+        assert_eq!(resolve(&matches), Some(vec![2, 3, 5, 8, 9]));
+
+        // ((UNIT=a AND PRIORITY=0) OR PRIORITY=2) AND (UNIT=b)
+        matches.add_conjunction().add_match(UNIT, "b");
+        assert_eq!(resolve(&matches), Some(vec![2, 8]));
+    }
+
+    #[test]
+    fn test_parse() {
+        let parsed = Matches::parse(["UNIT=a", "PRIORITY=0", "+", "PRIORITY=2"]).unwrap();
+        let mut matches = Matches::new();
+        matches
+            .add_match(UNIT, "a")
+            .add_match(PRIORITY, "0")
+            .add_disjunction()
+            .add_match(PRIORITY, "2");
+        assert_eq!(parsed, matches);
+
+        let mut protected = Matches::new();
+        protected
+            .add_protected_match(SYSTEMD_UNIT, "a.service")
+            .add_protected_match(PID, "1");
+        assert_eq!(
+            Matches::parse(["_SYSTEMD_UNIT=a.service", "_PID=1"]),
+            Ok(protected)
+        );
+        assert!(Matches::parse(["UNIT=a=b"]).is_ok());
+
+        let error = |arg: &str| Matches::parse(["UNIT=a", arg]).unwrap_err();
+        assert_eq!(error("UNIT").arg(), "UNIT");
+        assert_eq!(error("unit=a").arg(), "unit=a");
+        assert_eq!(
+            error("1UNIT=a").to_string(),
+            "`1UNIT=a` is not a match, expected `FIELD=value` or `+`"
+        );
+    }
+
+    #[test]
+    fn test_protected_field() {
+        for field in [
+            PID,
+            UID,
+            GID,
+            COMM,
+            EXE,
+            CMDLINE,
+            TRANSPORT,
+            HOSTNAME,
+            BOOT_ID,
+            MACHINE_ID,
+            SYSTEMD_CGROUP,
+            SYSTEMD_SLICE,
+            SYSTEMD_UNIT,
+            SYSTEMD_USER_UNIT,
+            SYSTEMD_INVOCATION_ID,
+        ] {
+            assert_eq!(ProtectedField::validate(field.as_str()), Some(field));
+        }
+        assert!(ProtectedField::validate("PID").is_none());
+        assert!(ProtectedField::validate("__CURSOR").is_none());
+        assert!(ProtectedField::validate("_pid").is_none());
+        assert!(ProtectedField::validate(&format!("_{}", "A".repeat(64))).is_none());
+    }
+}
@@ -1,5 +1,4 @@
-const MEMFD_FILENAME: &std::ffi::CStr =
-    unsafe { std::ffi::CStr::from_bytes_with_nul_unchecked(b"journald\0") };
+const MEMFD_FILENAME: &std::ffi::CStr = c"journald";
 
 #[derive(Debug)]
 pub struct SealableFile {
@@ -0,0 +1,149 @@
+//! Records panics in the journal.
+//!
+//! ```no_run
+//! journaled::panic::install_hook().unwrap();
+//!
+//! panic!("unrecoverable");
+//! ```
+
+const THREAD_NAME: crate::raw::Field = crate::raw::Field::unchecked("THREAD_NAME");
+const BACKTRACE: crate::raw::Field = crate::raw::Field::unchecked("BACKTRACE");
+
+/// Installs a panic hook that sends each panic to the journal, then calls the hook that was
+/// installed before it, e.g. the default one that prints to stderr.
+pub fn install_hook() -> std::io::Result<()> {
+    install_hook_with(crate::raw::JournalWriter::new()?);
+    Ok(())
+}
+
+/// Installs a panic hook as [[`install_hook`]], using an already configured writer.
+///
+/// A panic is sent at [[`crate::raw::Priority::Critical`]] with its message, location, the name
+/// and `TID` of the thread that panicked, and a backtrace in `BACKTRACE`.  Backtraces are often
+/// larger than a datagram can hold, in which case they're sent through a memfd.
+pub fn install_hook_with(writer: crate::raw::JournalWriter) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(hook(
+        move |entry| writer.send_bytes(entry.values()),
+        previous,
+    )));
+}
+
+/// The hook that sends each panic's fields to `send`, then calls `previous`.
+fn hook<S>(
+    send: S,
+    previous: Box<dyn Fn(&std::panic::PanicHookInfo<'_>) + Sync + Send + 'static>,
+) -> impl Fn(&std::panic::PanicHookInfo<'_>) + Sync + Send + 'static
+where
+    S: Fn(&crate::entry::Entry) -> std::io::Result<()> + Sync + Send + 'static,
+{
+    move |info| {
+        if let Err(err) = send(&entry(info)) {
+            eprintln!("logging panic failed: {}", err);
+        }
+        previous(info);
+    }
+}
+
+/// The fields that describe a panic.
+fn entry(info: &std::panic::PanicHookInfo<'_>) -> crate::entry::Entry {
+    let thread = std::thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+    let payload = payload_str(info.payload());
+    let mut entry = crate::entry::Entry::new()
+        .with(
+            crate::raw::PRIORITY,
+            crate::raw::Priority::Critical.as_str(),
+        )
+        .with(
+            crate::raw::MESSAGE,
+            format!("thread '{}' panicked: {}", thread_name, payload),
+        )
+        .with(THREAD_NAME, thread_name)
+        .with(crate::raw::TID, nix::unistd::gettid().to_string())
+        .with(
+            BACKTRACE,
+            std::backtrace::Backtrace::force_capture().to_string(),
+        );
+    if let Some(location) = info.location() {
+        entry.push(crate::raw::CODE_FILE, location.file());
+        entry.push(crate::raw::CODE_LINE, location.line().to_string());
+    }
+    entry
+}
+
+/// The message a panic was raised with, which is either a `&str` or a `String` for panics from
+/// `panic!`.
+fn payload_str(payload: &(dyn std::any::Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message,
+            None => "Box<dyn Any>",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_str() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new("static");
+        assert_eq!(payload_str(payload.as_ref()), "static");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(String::from("formatted"));
+        assert_eq!(payload_str(payload.as_ref()), "formatted");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(1);
+        assert_eq!(payload_str(payload.as_ref()), "Box<dyn Any>");
+    }
+
+    #[test]
+    fn test_hook() {
+        let entries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let chained = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let default = std::panic::take_hook();
+        std::panic::set_hook(Box::new(hook(
+            {
+                let entries = entries.clone();
+                move |entry| {
+                    entries.lock().unwrap().push(entry.clone());
+                    Ok(())
+                }
+            },
+            {
+                let chained = chained.clone();
+                Box::new(move |_| chained.store(true, std::sync::atomic::Ordering::SeqCst))
+            },
+        )));
+        let (result, tid) = std::thread::Builder::new()
+            .name("test-hook".to_owned())
+            .spawn(|| {
+                let tid = nix::unistd::gettid().to_string();
+                (std::panic::catch_unwind(|| panic!("hooked {}", 1)), tid)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        std::panic::set_hook(default);
+        assert!(result.is_err());
+        assert!(chained.load(std::sync::atomic::Ordering::SeqCst));
+
+        let entries = entries.lock().unwrap();
+        let entry = entries
+            .iter()
+            .find(|entry| entry.get(THREAD_NAME) == Some(b"test-hook".as_slice()))
+            .expect("the panic was sent");
+        let get = |field| std::str::from_utf8(entry.get(field).unwrap()).unwrap();
+        assert_eq!(get(crate::raw::PRIORITY), "2");
+        assert_eq!(
+            get(crate::raw::MESSAGE),
+            "thread 'test-hook' panicked: hooked 1"
+        );
+        assert_eq!(get(crate::raw::CODE_FILE), file!());
+        assert!(get(crate::raw::CODE_LINE).parse::<u32>().is_ok());
+        assert_eq!(get(crate::raw::TID), tid);
+        assert!(!get(BACKTRACE).is_empty());
+    }
+}
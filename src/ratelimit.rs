@@ -0,0 +1,322 @@
+//! Client side rate limiting of entries, as configured by
+//! [[`crate::raw::JournalWriter::with_rate_limit`]].
+//!
+//! journald limits the rate of each service as a whole, so a single noisy loop can cause every
+//! other entry to be dropped.  Limiting each call site separately keeps the rest flowing.
+
+const SUPPRESSED_COUNT: crate::raw::Field = crate::raw::Field::unchecked("SUPPRESSED_COUNT");
+const SUPPRESSED_MESSAGE_ID: crate::raw::Field =
+    crate::raw::Field::unchecked("SUPPRESSED_MESSAGE_ID");
+
+crate::message_id! {
+    /// Identifies the summaries of entries that were suppressed by a [[`RateLimit`]].
+    pub SUPPRESSED = "c2e175f55dcc4001acffe40f3f0a5d0f";
+}
+
+/// RateLimit is a token bucket that allows `burst` entries at once, refilling at `burst` entries
+/// per `interval`.
+///
+/// Each `MESSAGE_ID`, or each call site as given by `CODE_FILE` and `CODE_LINE` for entries
+/// without one, has a bucket of its own.  Entries with neither aren't limited.  Once an
+/// `interval` has passed since a bucket started suppressing entries, the next entry sent by the
+/// writer is preceded by a summary of how many were suppressed, with a `MESSAGE_ID` of
+/// [[`SUPPRESSED`]].  Summaries that are still pending when the writer stops sending entries are
+/// sent by [[`crate::raw::JournalWriter::flush`]].
+///
+/// ```no_run
+/// let limit = journaled::ratelimit::RateLimit::new(10, std::time::Duration::from_secs(30));
+/// let journal = journaled::raw::JournalWriter::new()
+///     .unwrap()
+///     .with_rate_limit(limit);
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    burst: u32,
+    interval: std::time::Duration,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, interval: std::time::Duration) -> Self {
+        Self { burst, interval }
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    pub fn interval(&self) -> std::time::Duration {
+        self.interval
+    }
+
+    /// Tokens added to a bucket per second.
+    fn rate(&self) -> f64 {
+        f64::from(self.burst) / self.interval.as_secs_f64()
+    }
+}
+
+/// Key identifies the bucket that an entry is counted against.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Key {
+    MessageId(Vec<u8>),
+    CallSite { file: Vec<u8>, line: Vec<u8> },
+}
+
+impl Key {
+    fn from_values<'a, I, V>(values: I) -> Option<Self>
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<[u8]>,
+    {
+        let mut file = None;
+        let mut line = None;
+        for (field, value) in values {
+            match field {
+                crate::raw::MESSAGE_ID => return Some(Self::MessageId(value.as_ref().to_vec())),
+                crate::raw::CODE_FILE => file = Some(value.as_ref().to_vec()),
+                crate::raw::CODE_LINE => line = Some(value.as_ref().to_vec()),
+                _ => (),
+            }
+        }
+        Some(Self::CallSite {
+            file: file?,
+            line: line?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: std::time::Instant,
+    suppressed: u64,
+    suppressed_since: std::time::Instant,
+}
+
+impl Bucket {
+    /// Adds the tokens that have accumulated since the bucket was last updated.
+    fn refill(&mut self, limit: &RateLimit, now: std::time::Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * limit.rate()).min(f64::from(limit.burst));
+        self.updated = now;
+    }
+}
+
+/// Summary describes the entries suppressed for a key.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Summary {
+    key: Key,
+    message: String,
+    count: String,
+}
+
+impl Summary {
+    fn new(key: Key, suppressed: u64) -> Self {
+        Self {
+            key,
+            message: format!("Suppressed {} messages", suppressed),
+            count: suppressed.to_string(),
+        }
+    }
+
+    /// The fields of the summary entry, which identify what was suppressed by the `MESSAGE_ID`
+    /// or call site.
+    pub(crate) fn values(
+        &self,
+    ) -> impl Iterator<Item = (crate::raw::Field<'static>, &[u8])> + Clone {
+        let key_values = match &self.key {
+            Key::MessageId(id) => [Some((SUPPRESSED_MESSAGE_ID, id.as_slice())), None],
+            Key::CallSite { file, line } => [
+                Some((crate::raw::CODE_FILE, file.as_slice())),
+                Some((crate::raw::CODE_LINE, line.as_slice())),
+            ],
+        };
+        [
+            (crate::raw::MESSAGE_ID, SUPPRESSED.as_str().as_bytes()),
+            (
+                crate::raw::PRIORITY,
+                crate::raw::Priority::Warning.as_str().as_bytes(),
+            ),
+            (crate::raw::MESSAGE, self.message.as_bytes()),
+            (SUPPRESSED_COUNT, self.count.as_bytes()),
+        ]
+        .into_iter()
+        .chain(key_values.into_iter().flatten())
+    }
+}
+
+/// RateLimiter keeps the buckets of a writer.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    buckets: std::sync::Mutex<std::collections::HashMap<Key, Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Takes a token for the entry, returning whether it should be sent.
+    pub(crate) fn allow<'a, I, V>(&self, values: I, now: std::time::Instant) -> bool
+    where
+        I: Iterator<Item = (crate::raw::Field<'a>, V)>,
+        V: AsRef<[u8]>,
+    {
+        let key = match Key::from_values(values) {
+            Some(key) => key,
+            None => return true,
+        };
+
+        let mut buckets = self.lock();
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: f64::from(self.limit.burst),
+            updated: now,
+            suppressed: 0,
+            suppressed_since: now,
+        });
+
+        bucket.refill(&self.limit, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            if bucket.suppressed == 0 {
+                bucket.suppressed_since = now;
+            }
+            bucket.suppressed += 1;
+            false
+        }
+    }
+
+    /// Takes the summaries of the keys that have been suppressing entries for an interval.
+    ///
+    /// Buckets that have refilled and have nothing to report are removed, since a new bucket
+    /// would be the same, so only the keys that were used recently are kept.
+    pub(crate) fn summaries(&self, now: std::time::Instant) -> Vec<Summary> {
+        let summaries = self.take(|bucket| {
+            now.saturating_duration_since(bucket.suppressed_since) >= self.limit.interval
+        });
+        self.lock().retain(|_, bucket| {
+            bucket.refill(&self.limit, now);
+            bucket.suppressed > 0 || bucket.tokens < f64::from(self.limit.burst)
+        });
+        summaries
+    }
+
+    /// Takes the summaries of every key that has suppressed entries.
+    pub(crate) fn pending(&self) -> Vec<Summary> {
+        self.take(|_| true)
+    }
+
+    fn take<F>(&self, f: F) -> Vec<Summary>
+    where
+        F: Fn(&Bucket) -> bool,
+    {
+        let mut buckets = self.lock();
+        buckets
+            .iter_mut()
+            .filter(|(_, bucket)| bucket.suppressed > 0 && f(bucket))
+            .map(|(key, bucket)| Summary::new(key.clone(), std::mem::take(&mut bucket.suppressed)))
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, std::collections::HashMap<Key, Bucket>> {
+        self.buckets.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_site(line: &str) -> [(crate::raw::Field<'static>, &str); 3] {
+        [
+            (crate::raw::MESSAGE, "noisy"),
+            (crate::raw::CODE_FILE, "main.rs"),
+            (crate::raw::CODE_LINE, line),
+        ]
+    }
+
+    #[test]
+    fn test_allow() {
+        let limiter = RateLimiter::new(RateLimit::new(2, std::time::Duration::from_secs(10)));
+        let start = std::time::Instant::now();
+        let allow = |values: &[(crate::raw::Field, &str)], secs| {
+            limiter.allow(
+                values.iter().copied(),
+                start + std::time::Duration::from_secs(secs),
+            )
+        };
+
+        assert!(allow(&call_site("1"), 0));
+        assert!(allow(&call_site("1"), 0));
+        assert!(!allow(&call_site("1"), 0));
+        // Other call sites and entries without one have buckets of their own.
+        assert!(allow(&call_site("2"), 0));
+        assert!(allow(&[(crate::raw::MESSAGE, "unkeyed")], 0));
+        assert!(allow(&[(crate::raw::MESSAGE, "unkeyed")], 0));
+        assert!(allow(&[(crate::raw::MESSAGE, "unkeyed")], 0));
+        // A token is added every 5 seconds.
+        assert!(!allow(&call_site("1"), 4));
+        assert!(allow(&call_site("1"), 5));
+        assert!(!allow(&call_site("1"), 5));
+    }
+
+    #[test]
+    fn test_message_id() {
+        let limiter = RateLimiter::new(RateLimit::new(1, std::time::Duration::from_secs(10)));
+        let now = std::time::Instant::now();
+        let entry = |line| {
+            [
+                (crate::raw::MESSAGE_ID, "a2a5ac4c5b6e4ffa9a4df4bdf4bb1c42"),
+                (crate::raw::CODE_FILE, "main.rs"),
+                (crate::raw::CODE_LINE, line),
+            ]
+        };
+
+        assert!(limiter.allow(entry("1").into_iter(), now));
+        assert!(!limiter.allow(entry("2").into_iter(), now));
+    }
+
+    #[test]
+    fn test_summaries() {
+        let limiter = RateLimiter::new(RateLimit::new(1, std::time::Duration::from_secs(10)));
+        let start = std::time::Instant::now();
+        let at = |secs| start + std::time::Duration::from_secs(secs);
+
+        assert!(limiter.allow(call_site("1").into_iter(), at(0)));
+        assert!(!limiter.allow(call_site("1").into_iter(), at(1)));
+        assert!(!limiter.allow(call_site("1").into_iter(), at(2)));
+        assert!(limiter.summaries(at(10)).is_empty());
+
+        let summaries = limiter.summaries(at(11));
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(
+            summaries[0]
+                .values()
+                .map(|(field, value)| (field.as_str(), value))
+                .collect::<Vec<_>>(),
+            [
+                ("MESSAGE_ID", SUPPRESSED.as_str().as_bytes()),
+                ("PRIORITY", b"4"),
+                ("MESSAGE", b"Suppressed 2 messages"),
+                ("SUPPRESSED_COUNT", b"2"),
+                ("CODE_FILE", b"main.rs"),
+                ("CODE_LINE", b"1"),
+            ]
+        );
+        assert!(limiter.summaries(at(30)).is_empty());
+        // The bucket refilled by then, so it was forgotten.
+        assert!(limiter.lock().is_empty());
+
+        assert!(limiter.allow(call_site("1").into_iter(), at(30)));
+        assert!(!limiter.allow(call_site("1").into_iter(), at(30)));
+        assert_eq!(limiter.pending().len(), 1);
+        assert!(limiter.pending().is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 const JOURNALD_PATH: &str = "/run/systemd/journal/socket";
-const FIELD_LEN_MAX: usize = 64;
+pub(crate) const FIELD_LEN_MAX: usize = 64;
 
 pub const MESSAGE: Field = Field::unchecked("MESSAGE");
 pub const MESSAGE_ID: Field = Field::unchecked("MESSAGE_ID");
@@ -46,7 +46,7 @@ fn is_valid_field(field: &str) -> bool {
 /// Field represents an borrowed value of an already validated string.  systemd places specific
 /// requirements on characters that can be used in its key, value pairs (though they are not
 /// required to be unique).
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Field<'a> {
     inner: &'a str,
 }
@@ -78,6 +78,10 @@ impl<'a> Field<'a> {
         is_valid_field(self.inner)
     }
 
+    pub fn as_str(&self) -> &'a str {
+        self.inner
+    }
+
     /// Capacity required in bytes when serialized.
     fn required_capacity(&self) -> usize {
         self.inner.len()
@@ -87,12 +91,21 @@ impl<'a> Field<'a> {
 /// OwnedField represents an already validated string.  Similar to Field, its value already conforms
 /// to the constraints needed by systemd.  This can be used when constructing a field value that may
 /// not be known at compile time.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct OwnedField {
     inner: String,
 }
 
 impl OwnedField {
+    /// The field value is checked by [[`is_valid_field`]] and an OwnedField is returned if true.
+    pub fn validate(inner: String) -> Option<Self> {
+        if is_valid_field(&inner) {
+            Some(Self { inner })
+        } else {
+            None
+        }
+    }
+
     pub fn sanitize<S>(field: S) -> Option<Self>
     where
         S: AsRef<str>,
@@ -138,6 +151,18 @@ impl OwnedField {
             inner: prefix.inner.chars().chain(iter).collect(),
         }
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl<'a> std::convert::From<Field<'a>> for OwnedField {
+    fn from(field: Field<'a>) -> OwnedField {
+        OwnedField {
+            inner: field.inner.to_owned(),
+        }
+    }
 }
 
 impl<'a> std::convert::From<&'a OwnedField> for Field<'a> {
@@ -148,19 +173,47 @@ impl<'a> std::convert::From<&'a OwnedField> for Field<'a> {
     }
 }
 
-/// Required capacity in bytes when a string is serialized for the journal with a [[`Field`]].
-fn required_capacity(value: impl AsRef<str>) -> usize {
+/// Required capacity in bytes when a value is serialized for the journal with a [[`Field`]].
+fn required_capacity(value: impl AsRef<[u8]>) -> usize {
     value.as_ref().len() // payload length
             + 2 // separator ('\n') and end new line
             + 8 // u64 encoded len
 }
 
-/// Required capacity in bytes when a string is serialized for the journal with a [[`Field`]].
-fn encoded_len(value: impl AsRef<str>) -> [u8; 8] {
+/// Length of a value as it's encoded ahead of the payload.
+fn encoded_len(value: impl AsRef<[u8]>) -> [u8; 8] {
     (value.as_ref().len() as u64).to_le_bytes()
 }
 
+/// Adapts a string value for [[`JournalWriter::send_bytes`]] and the export format.
+#[derive(Clone)]
+pub(crate) struct StrValue<V>(pub(crate) V);
+
+impl<V> AsRef<[u8]> for StrValue<V>
+where
+    V: AsRef<str>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref().as_bytes()
+    }
+}
+
+/// Serializes a field and its value using the binary form of the native protocol, which allows
+/// the value to contain new lines.
+pub(crate) fn encode(data: &mut Vec<u8>, field: &Field, value: impl AsRef<[u8]>) {
+    data.extend(field.inner.as_bytes());
+    data.push(b'\n');
+    data.extend(encoded_len(&value));
+    data.extend(value.as_ref());
+    data.push(b'\n');
+}
+
 /// Priority is an enum for the syslog-style values used by the systemd journal.
+///
+/// Priorities are ordered the same way as their syslog values, so [[`Priority::Emergency`]] is the
+/// lowest and [[`Priority::Debug`]] the highest.  A message is more severe than a threshold when
+/// `priority <= threshold`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Priority {
     Emergency,
     Alert,
@@ -173,7 +226,19 @@ pub enum Priority {
 }
 
 impl Priority {
-    const fn as_str(&self) -> &'static str {
+    const ALL: [Priority; 8] = [
+        Priority::Emergency,
+        Priority::Alert,
+        Priority::Critical,
+        Priority::Error,
+        Priority::Warning,
+        Priority::Notice,
+        Priority::Info,
+        Priority::Debug,
+    ];
+
+    /// The value as it's encoded in the `PRIORITY` field.
+    pub const fn as_str(&self) -> &'static str {
         match &self {
             Priority::Emergency => "0",
             Priority::Alert => "1",
@@ -186,24 +251,403 @@ impl Priority {
         }
     }
 
+    pub const fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    /// The syslog name, as accepted by `journalctl --priority`.
+    pub const fn name(&self) -> &'static str {
+        match &self {
+            Priority::Emergency => "emerg",
+            Priority::Alert => "alert",
+            Priority::Critical => "crit",
+            Priority::Error => "err",
+            Priority::Warning => "warning",
+            Priority::Notice => "notice",
+            Priority::Info => "info",
+            Priority::Debug => "debug",
+        }
+    }
+
     /// Is used to construct a tuple to be passed into [[`JournalWriter`]].
     pub const fn as_value(&self) -> (crate::raw::Field<'static>, &'static str) {
         (crate::raw::PRIORITY, self.as_str())
     }
 }
 
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Priority {
+    type Err = ParsePriorityError;
+
+    /// Parses either the numeric value or the syslog name, along with the common aliases, e.g.
+    /// "4", "warning", and "warn" are all [[`Priority::Warning`]].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(value) = s.parse::<u8>() {
+            return Self::try_from(value);
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "emerg" | "emergency" | "panic" => Ok(Priority::Emergency),
+            "alert" => Ok(Priority::Alert),
+            "crit" | "critical" => Ok(Priority::Critical),
+            "err" | "error" => Ok(Priority::Error),
+            "warning" | "warn" => Ok(Priority::Warning),
+            "notice" => Ok(Priority::Notice),
+            "info" => Ok(Priority::Info),
+            "debug" => Ok(Priority::Debug),
+            _ => Err(ParsePriorityError),
+        }
+    }
+}
+
+impl std::convert::TryFrom<u8> for Priority {
+    type Error = ParsePriorityError;
+
+    fn try_from(value: u8) -> Result<Self, ParsePriorityError> {
+        Self::ALL
+            .get(usize::from(value))
+            .copied()
+            .ok_or(ParsePriorityError)
+    }
+}
+
+impl std::convert::From<Priority> for u8 {
+    fn from(priority: Priority) -> u8 {
+        priority.as_u8()
+    }
+}
+
+impl std::convert::From<Priority> for &'static str {
+    fn from(priority: Priority) -> &'static str {
+        priority.as_str()
+    }
+}
+
+/// Error returned when a value isn't a known [[`Priority`]].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParsePriorityError;
+
+impl std::fmt::Display for ParsePriorityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("expected a priority from 0 to 7 or a syslog level name")
+    }
+}
+
+impl std::error::Error for ParsePriorityError {}
+
+/// Level is a level of a logging crate, whose records are sent with the priorities given by a
+/// [[`PriorityMap`]].
+pub trait Level: Copy + Into<Priority> + 'static {
+    /// Every level, from the most severe.
+    const ALL: &'static [Self];
+
+    /// The position of the level in [[`Level::ALL`]].
+    fn index(self) -> usize;
+}
+
+/// PriorityMap decides the priority that records of each level are sent with, or whether they're
+/// sent at all.  By default levels are mapped by their `Into<Priority>` conversion.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PriorityMap<L> {
+    priorities: [Option<Priority>; Priority::ALL.len()],
+    levels: std::marker::PhantomData<L>,
+}
+
+impl<L> PriorityMap<L>
+where
+    L: Level,
+{
+    pub fn map(mut self, level: L, priority: Priority) -> Self {
+        self.priorities[level.index()] = Some(priority);
+        self
+    }
+
+    /// Stops records of a level from being sent to the journal.
+    pub fn discard(mut self, level: L) -> Self {
+        self.priorities[level.index()] = None;
+        self
+    }
+
+    pub fn get(&self, level: L) -> Option<Priority> {
+        self.priorities[level.index()]
+    }
+}
+
+impl<L> Default for PriorityMap<L>
+where
+    L: Level,
+{
+    fn default() -> Self {
+        let mut priorities = [None; Priority::ALL.len()];
+        for level in L::ALL {
+            priorities[level.index()] = Some((*level).into());
+        }
+        Self {
+            priorities,
+            levels: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Facility is the syslog facility of an entry, encoded in the `SYSLOG_FACILITY` field.
+///
+/// The names match those used by `systemd` and `logger`, including the facilities that are
+/// reserved by BSD systems.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Facility {
+    Kern,
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    AuthPriv,
+    Ftp,
+    Ntp,
+    Security,
+    Console,
+    SolarisCron,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    const ALL: [Facility; 24] = [
+        Facility::Kern,
+        Facility::User,
+        Facility::Mail,
+        Facility::Daemon,
+        Facility::Auth,
+        Facility::Syslog,
+        Facility::Lpr,
+        Facility::News,
+        Facility::Uucp,
+        Facility::Cron,
+        Facility::AuthPriv,
+        Facility::Ftp,
+        Facility::Ntp,
+        Facility::Security,
+        Facility::Console,
+        Facility::SolarisCron,
+        Facility::Local0,
+        Facility::Local1,
+        Facility::Local2,
+        Facility::Local3,
+        Facility::Local4,
+        Facility::Local5,
+        Facility::Local6,
+        Facility::Local7,
+    ];
+
+    /// The value as it's encoded in the `SYSLOG_FACILITY` field.
+    pub const fn as_str(&self) -> &'static str {
+        match &self {
+            Facility::Kern => "0",
+            Facility::User => "1",
+            Facility::Mail => "2",
+            Facility::Daemon => "3",
+            Facility::Auth => "4",
+            Facility::Syslog => "5",
+            Facility::Lpr => "6",
+            Facility::News => "7",
+            Facility::Uucp => "8",
+            Facility::Cron => "9",
+            Facility::AuthPriv => "10",
+            Facility::Ftp => "11",
+            Facility::Ntp => "12",
+            Facility::Security => "13",
+            Facility::Console => "14",
+            Facility::SolarisCron => "15",
+            Facility::Local0 => "16",
+            Facility::Local1 => "17",
+            Facility::Local2 => "18",
+            Facility::Local3 => "19",
+            Facility::Local4 => "20",
+            Facility::Local5 => "21",
+            Facility::Local6 => "22",
+            Facility::Local7 => "23",
+        }
+    }
+
+    pub const fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    pub const fn name(&self) -> &'static str {
+        match &self {
+            Facility::Kern => "kern",
+            Facility::User => "user",
+            Facility::Mail => "mail",
+            Facility::Daemon => "daemon",
+            Facility::Auth => "auth",
+            Facility::Syslog => "syslog",
+            Facility::Lpr => "lpr",
+            Facility::News => "news",
+            Facility::Uucp => "uucp",
+            Facility::Cron => "cron",
+            Facility::AuthPriv => "authpriv",
+            Facility::Ftp => "ftp",
+            Facility::Ntp => "ntp",
+            Facility::Security => "security",
+            Facility::Console => "console",
+            Facility::SolarisCron => "solaris-cron",
+            Facility::Local0 => "local0",
+            Facility::Local1 => "local1",
+            Facility::Local2 => "local2",
+            Facility::Local3 => "local3",
+            Facility::Local4 => "local4",
+            Facility::Local5 => "local5",
+            Facility::Local6 => "local6",
+            Facility::Local7 => "local7",
+        }
+    }
+
+    /// Is used to construct a tuple to be passed into [[`JournalWriter`]].
+    pub const fn as_value(&self) -> (crate::raw::Field<'static>, &'static str) {
+        (crate::raw::SYSLOG_FACILITY, self.as_str())
+    }
+}
+
+impl std::fmt::Display for Facility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Facility {
+    type Err = ParseFacilityError;
+
+    /// Parses either the numeric value or the name, case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(value) = s.parse::<u8>() {
+            return Self::try_from(value);
+        }
+        Self::ALL
+            .iter()
+            .find(|facility| facility.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or(ParseFacilityError)
+    }
+}
+
+impl std::convert::TryFrom<u8> for Facility {
+    type Error = ParseFacilityError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::ALL
+            .get(usize::from(value))
+            .copied()
+            .ok_or(ParseFacilityError)
+    }
+}
+
+impl std::convert::From<Facility> for u8 {
+    fn from(facility: Facility) -> u8 {
+        facility.as_u8()
+    }
+}
+
+impl std::convert::From<Facility> for &'static str {
+    fn from(facility: Facility) -> &'static str {
+        facility.as_str()
+    }
+}
+
+/// Error returned when a value isn't a known [[`Facility`]].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseFacilityError;
+
+impl std::fmt::Display for ParseFacilityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("expected a facility from 0 to 23 or a syslog facility name")
+    }
+}
+
+impl std::error::Error for ParseFacilityError {}
+
+#[derive(Debug)]
 pub struct JournalWriter {
     socket: std::os::unix::net::UnixDatagram,
+    facility: Option<Facility>,
+    rate_limiter: Option<crate::ratelimit::RateLimiter>,
+    deduplicator: Option<crate::dedup::Deduplicator>,
 }
 
 impl JournalWriter {
     pub fn new() -> std::io::Result<Self> {
         let socket = std::os::unix::net::UnixDatagram::unbound()?;
-        Ok(Self { socket })
+        Ok(Self {
+            socket,
+            facility: None,
+            rate_limiter: None,
+            deduplicator: None,
+        })
+    }
+
+    /// Includes `SYSLOG_FACILITY` in every entry sent by this writer that doesn't set its own, so
+    /// that services migrated from syslog keep their classification.
+    pub fn with_facility(mut self, facility: Facility) -> Self {
+        self.facility = Some(facility);
+        self
+    }
+
+    pub fn facility(&self) -> Option<Facility> {
+        self.facility
+    }
+
+    /// Limits the rate that entries are sent at for each `MESSAGE_ID` or call site, as described
+    /// by [[`crate::ratelimit::RateLimit`]].
+    pub fn with_rate_limit(mut self, limit: crate::ratelimit::RateLimit) -> Self {
+        self.rate_limiter = Some(crate::ratelimit::RateLimiter::new(limit));
+        self
+    }
+
+    /// Collapses identical consecutive entries into a summary of how many times they were
+    /// repeated, as described by [[`crate::dedup::Dedup`]].
+    ///
+    /// Entries are compared before any rate limit is applied, so repeats don't use it up.
+    pub fn with_dedup(mut self, dedup: crate::dedup::Dedup) -> Self {
+        self.deduplicator = Some(crate::dedup::Deduplicator::new(dedup));
+        self
+    }
+
+    /// Sends the summaries of any entries that are being collapsed or suppressed.
+    ///
+    /// Summaries are otherwise only sent ahead of later entries, so this has to be called before
+    /// exiting, or they're lost.  Writers installed as a logger are never dropped, and are flushed
+    /// through the logging crate instead, e.g. with `log::logger().flush()`.
+    pub fn flush(&self) -> std::io::Result<()> {
+        if let Some(summary) = self
+            .deduplicator
+            .as_ref()
+            .and_then(crate::dedup::Deduplicator::pending)
+        {
+            self.send_entry(summary.values())?;
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            for summary in limiter.pending() {
+                self.send_entry(summary.values())?;
+            }
+        }
+        Ok(())
     }
 
     pub fn check(&self) -> std::io::Result<()> {
-        self.send(([] as [(Field, &str); 0]).into_iter())
+        self.send_data(&[]).map(|_| ())
     }
 
     pub fn send<'a, I, V>(&self, values: I) -> std::io::Result<()>
@@ -211,39 +655,95 @@ impl JournalWriter {
         I: Iterator<Item = (Field<'a>, V)> + Clone,
         V: AsRef<str>,
     {
+        self.send_bytes(values.map(|(field, value)| (field, StrValue(value))))
+    }
+
+    /// Sends values that aren't necessarily valid UTF-8, which the journal stores as binary data.
+    pub fn send_bytes<'a, I, V>(&self, values: I) -> std::io::Result<()>
+    where
+        I: Iterator<Item = (Field<'a>, V)> + Clone,
+        V: AsRef<[u8]>,
+    {
+        let now = std::time::Instant::now();
+        if let Some(deduplicator) = &self.deduplicator {
+            let (send, summary) = deduplicator.check(values.clone(), now);
+            if let Some(summary) = summary {
+                self.send_entry(summary.values())?;
+            }
+            if !send {
+                return Ok(());
+            }
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            for summary in limiter.summaries(now) {
+                self.send_entry(summary.values())?;
+            }
+            if !limiter.allow(values.clone(), now) {
+                return Ok(());
+            }
+        }
+
+        self.send_entry(values)
+    }
+
+    /// Encodes and sends an entry, after any limits have been applied.
+    fn send_entry<'a, I, V>(&self, values: I) -> std::io::Result<()>
+    where
+        I: Iterator<Item = (Field<'a>, V)> + Clone,
+        V: AsRef<[u8]>,
+    {
+        let defaults = self.default_values(values.clone());
         let data = {
             let mut data = Vec::<u8>::new();
             data.reserve_exact(
-                values
+                defaults
                     .clone()
                     .map(|(field, value)| field.required_capacity() + required_capacity(value))
+                    .chain(values.clone().map(|(field, value)| {
+                        field.required_capacity() + required_capacity(value.as_ref())
+                    }))
                     .sum::<usize>(),
             );
+            for (ref field, value) in defaults {
+                encode(&mut data, field, value);
+            }
             for (ref field, ref value) in values {
-                data.extend(field.inner.as_bytes());
-                data.push(b'\n');
-                data.extend(encoded_len(value));
-                data.extend(value.as_ref().as_bytes());
-                data.push(b'\n');
+                encode(&mut data, field, value.as_ref());
             }
             data
         };
 
+        self.send_data(&data).map(|_| ())
+    }
+
+    /// Fields configured on the writer, which are sent ahead of the values of every entry that
+    /// doesn't have them already.
+    fn default_values<'a, I, V>(
+        &self,
+        mut values: I,
+    ) -> impl Iterator<Item = (Field<'static>, &'static str)> + Clone
+    where
+        I: Iterator<Item = (Field<'a>, V)>,
+    {
+        self.facility
+            .filter(|_| values.all(|(field, _)| field != SYSLOG_FACILITY))
+            .map(|facility| facility.as_value())
+            .into_iter()
+    }
+
+    fn send_data(&self, data: &[u8]) -> std::io::Result<usize> {
         // Try sending directly via the socket first.  If that fails, due to the message being too
         // large, send using a sealed memfd.  The max size is system dependent, which we could try
         // to figure out and store.  In lieu of that, just always try the fast path first.
-        self.socket
-            .send_to(&data, JOURNALD_PATH)
-            .or_else(|err| {
-                if let Some(nix::errno::Errno::EMSGSIZE) =
-                    err.raw_os_error().map(nix::errno::Errno::from_i32)
-                {
-                    self.send_by_memfd(&data)
-                } else {
-                    Err(err)
-                }
-            })
-            .map(|_| ())
+        self.socket.send_to(data, JOURNALD_PATH).or_else(|err| {
+            if let Some(nix::errno::Errno::EMSGSIZE) =
+                err.raw_os_error().map(nix::errno::Errno::from_i32)
+            {
+                self.send_by_memfd(data)
+            } else {
+                Err(err)
+            }
+        })
     }
 
     fn send_by_memfd(&self, data: &[u8]) -> std::io::Result<usize> {
@@ -346,6 +846,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_priority_parse() {
+        assert_eq!("0".parse(), Ok(Priority::Emergency));
+        assert_eq!("7".parse(), Ok(Priority::Debug));
+        assert_eq!("warning".parse(), Ok(Priority::Warning));
+        assert_eq!("WARN".parse(), Ok(Priority::Warning));
+        assert_eq!("err".parse(), Ok(Priority::Error));
+        assert_eq!("crit".parse(), Ok(Priority::Critical));
+        assert!("8".parse::<Priority>().is_err());
+        assert!("verbose".parse::<Priority>().is_err());
+
+        assert_eq!(Priority::try_from(5), Ok(Priority::Notice));
+        assert!(Priority::try_from(8).is_err());
+    }
+
+    #[test]
+    fn test_priority_round_trip() {
+        for priority in Priority::ALL {
+            assert_eq!(priority.to_string().parse(), Ok(priority));
+            assert_eq!(priority.as_str().parse(), Ok(priority));
+            assert_eq!(Priority::try_from(u8::from(priority)), Ok(priority));
+        }
+    }
+
+    #[test]
+    fn test_priority_ordering() {
+        assert!(Priority::Emergency < Priority::Error);
+        assert!(Priority::Info < Priority::Debug);
+        assert_eq!(Priority::ALL.iter().max(), Some(&Priority::Debug));
+    }
+
+    #[test]
+    fn test_facility_parse() {
+        assert_eq!("0".parse(), Ok(Facility::Kern));
+        assert_eq!("23".parse(), Ok(Facility::Local7));
+        assert_eq!("daemon".parse(), Ok(Facility::Daemon));
+        assert_eq!("LOCAL3".parse(), Ok(Facility::Local3));
+        assert_eq!("solaris-cron".parse(), Ok(Facility::SolarisCron));
+        assert!("24".parse::<Facility>().is_err());
+        assert!("local8".parse::<Facility>().is_err());
+    }
+
+    #[test]
+    fn test_facility_round_trip() {
+        for facility in Facility::ALL {
+            assert_eq!(facility.to_string().parse(), Ok(facility));
+            assert_eq!(facility.as_str().parse(), Ok(facility));
+            assert_eq!(Facility::try_from(u8::from(facility)), Ok(facility));
+        }
+    }
+
+    #[test]
+    fn test_default_facility() {
+        let journal = JournalWriter::new()
+            .unwrap()
+            .with_facility(Facility::Local3);
+        assert_eq!(
+            journal
+                .default_values([(MESSAGE, "a")].into_iter())
+                .collect::<Vec<_>>(),
+            [(SYSLOG_FACILITY, "19")]
+        );
+        assert_eq!(
+            journal
+                .default_values([(MESSAGE, "a"), (SYSLOG_FACILITY, "4")].into_iter())
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut data = Vec::new();
+        encode(&mut data, &MESSAGE, "a\nb");
+        assert_eq!(
+            data.len(),
+            MESSAGE.required_capacity() + required_capacity("a\nb")
+        );
+        assert_eq!(data, b"MESSAGE\n\x03\0\0\0\0\0\0\0a\nb\n");
+    }
+
     #[test]
     fn test_into_field() {
         assert_eq!(
@@ -0,0 +1,885 @@
+//! A [[`serde::Serializer`]] that turns structs and maps into journal fields.
+//!
+//! Each member becomes a field named by [[`crate::flatten`]], so `user_id` becomes `USER_ID`, and a
+//! `message` member becomes the entry's `MESSAGE`.  The members of nested structs and maps are
+//! named by their path, so `path` within `request` becomes `REQUEST__PATH`, like the key
+//! `request.path` in the `log` and `slog` integrations.  Members whose names collide are given
+//! suffixes, as by [[`crate::flatten::FieldNames`]].
+//! Scalars are written as text, sequences as one value of the same field per element, and bytes
+//! as binary values.  `None` and unit values are left out.  Enum variants with data are nested
+//! under the name of the variant, as serde does for externally tagged enums.
+//!
+//! ```no_run
+//! #[derive(serde::Serialize)]
+//! struct Request<'a> {
+//!     message: &'a str,
+//!     path: &'a str,
+//!     tags: &'a [&'a str],
+//!     user: Option<User>,
+//! }
+//!
+//! #[derive(serde::Serialize)]
+//! struct User {
+//!     id: u64,
+//! }
+//!
+//! let journal = journaled::raw::JournalWriter::new().unwrap();
+//! let request = Request {
+//!     message: "Handled request",
+//!     path: "/index.html",
+//!     tags: &["cached", "gzip"],
+//!     user: Some(User { id: 12 }),
+//! };
+//! // Sends MESSAGE, PATH, TAGS twice, and USER__ID.
+//! journaled::serde::send(&journal, &request).unwrap();
+//! ```
+
+/// Error returned when a value can't be serialized into fields.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// A scalar or sequence was serialized without a member name to give it, e.g. at the top
+    /// level.
+    MissingKey,
+    /// The path to a member had no letters, so it can't be made into a field name even with a
+    /// prefix.
+    InvalidKey(String),
+    /// A map key was not a string, character, integer, boolean, or unit variant.
+    KeyNotScalar,
+    /// The value's `Serialize` implementation failed.
+    Custom(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingKey => f.write_str("expected a struct or map at the top level"),
+            Error::InvalidKey(key) => write!(f, "`{}` can't be made into a field name", key),
+            Error::KeyNotScalar => f.write_str("expected map keys to be scalars"),
+            Error::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::ser::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// Serializer collects the fields of the values serialized into it.
+///
+/// Several values can be serialized into the same entry, e.g. a struct describing an event
+/// followed by one describing its context.
+#[derive(Clone, Debug, Default)]
+pub struct Serializer {
+    names: crate::flatten::FieldNames,
+    paths: std::collections::HashMap<Vec<String>, crate::raw::OwnedField>,
+    entry: crate::entry::Entry,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepends `prefix` to every field name, as [[`crate::raw::OwnedField::sanitize_with_prefix`]]
+    /// does.
+    pub fn with_prefix(prefix: crate::raw::Field) -> Self {
+        Self {
+            names: crate::flatten::FieldNames::default().with_prefix(prefix),
+            ..Self::default()
+        }
+    }
+
+    /// Adds the members of a struct or map to the entry.
+    pub fn serialize<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(ValueSerializer {
+            fields: self,
+            path: Vec::new(),
+        })
+    }
+
+    pub fn entry(&self) -> &crate::entry::Entry {
+        &self.entry
+    }
+
+    pub fn into_entry(self) -> crate::entry::Entry {
+        self.entry
+    }
+
+    /// Adds a value to the field for `path`, which is named the first time the path is seen so
+    /// that the elements of a sequence share it.
+    fn push(&mut self, path: &[String], value: Vec<u8>) -> Result<(), Error> {
+        if path.is_empty() {
+            return Err(Error::MissingKey);
+        }
+        let field = match self.paths.get(path) {
+            Some(field) => field.clone(),
+            None => {
+                let field = self.name(path)?;
+                self.paths.insert(path.to_vec(), field.clone());
+                field
+            }
+        };
+        self.entry.push(field, value);
+        Ok(())
+    }
+
+    fn name(&mut self, path: &[String]) -> Result<crate::raw::OwnedField, Error> {
+        let invalid = || Error::InvalidKey(path.join("."));
+        // A prefix would make any path valid, but the names should be the same with or without.
+        if !path
+            .iter()
+            .any(|segment| segment.chars().any(|c| c.is_ascii_alphabetic()))
+        {
+            return Err(invalid());
+        }
+        let field = self
+            .names
+            .flatten_path(path.iter().map(String::as_str))
+            .ok_or_else(invalid)?;
+        Ok(self
+            .names
+            .claim(field)
+            .expect("colliding names are suffixed"))
+    }
+}
+
+/// Serializes a struct or map into an entry.
+pub fn to_entry<T>(value: &T) -> Result<crate::entry::Entry, Error>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut serializer = Serializer::new();
+    serializer.serialize(value)?;
+    Ok(serializer.into_entry())
+}
+
+/// Serializes a struct or map and sends it as an entry.  Serialization errors are returned as
+/// [[`std::io::ErrorKind::InvalidInput`]].
+pub fn send<T>(writer: &crate::raw::JournalWriter, value: &T) -> std::io::Result<()>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let entry = to_entry(value)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    writer.send_bytes(entry.values())
+}
+
+/// The path to a member nested under `path`.
+fn join(path: &[String], key: &str) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(key.to_owned());
+    path
+}
+
+/// ValueSerializer writes a value to the field named by `path`, or its members to fields named
+/// after it.
+struct ValueSerializer<'a> {
+    fields: &'a mut Serializer,
+    path: Vec<String>,
+}
+
+impl<'a> ValueSerializer<'a> {
+    fn text<T>(self, value: T) -> Result<(), Error>
+    where
+        T: std::fmt::Display,
+    {
+        self.fields.push(&self.path, value.to_string().into_bytes())
+    }
+
+    fn compound(self, variant: Option<&str>) -> Compound<'a> {
+        let path = match variant {
+            Some(variant) => join(&self.path, variant),
+            None => self.path,
+        };
+        Compound {
+            fields: self.fields,
+            path,
+            key: None,
+        }
+    }
+}
+
+impl<'a> serde::Serializer for ValueSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.fields.push(&self.path, v.to_vec())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.text(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(ValueSerializer {
+            path: join(&self.path, variant),
+            fields: self.fields,
+        })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(variant)))
+    }
+}
+
+/// Compound serializes the elements of sequences, which share the field of the sequence, and the
+/// members of structs and maps, which are nested under it.
+struct Compound<'a> {
+    fields: &'a mut Serializer,
+    path: Vec<String>,
+    key: Option<String>,
+}
+
+impl<'a> Compound<'a> {
+    fn element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        if self.path.is_empty() {
+            return Err(Error::MissingKey);
+        }
+        value.serialize(ValueSerializer {
+            fields: self.fields,
+            path: self.path.clone(),
+        })
+    }
+
+    fn member<T>(&mut self, key: &str, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(ValueSerializer {
+            fields: self.fields,
+            path: join(&self.path, key),
+        })
+    }
+}
+
+impl<'a> serde::ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::Custom("value serialized before its key".to_owned()))?;
+        self.member(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.member(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> serde::ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.member(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// KeySerializer turns map keys into the text that's used to name their fields.
+struct KeySerializer;
+
+impl KeySerializer {
+    fn text<T>(value: T) -> Result<String, Error>
+    where
+        T: std::fmt::Display,
+    {
+        Ok(value.to_string())
+    }
+}
+
+impl serde::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = serde::ser::Impossible<String, Error>;
+    type SerializeTuple = serde::ser::Impossible<String, Error>;
+    type SerializeTupleStruct = serde::ser::Impossible<String, Error>;
+    type SerializeTupleVariant = serde::ser::Impossible<String, Error>;
+    type SerializeMap = serde::ser::Impossible<String, Error>;
+    type SerializeStruct = serde::ser::Impossible<String, Error>;
+    type SerializeStructVariant = serde::ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Self::text(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<String, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Self::text(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<String, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::KeyNotScalar)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::KeyNotScalar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    struct Event<'a> {
+        message: &'a str,
+        count: u32,
+        ok: bool,
+        ratio: f64,
+        missing: Option<u8>,
+        tags: Vec<&'a str>,
+        user: User,
+        kind: Kind,
+        state: State,
+        #[serde(with = "bytes")]
+        raw: &'a [u8],
+    }
+
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u64,
+        #[serde(rename = "emailAddress")]
+        email: Option<String>,
+    }
+
+    #[derive(serde::Serialize)]
+    enum Kind {
+        Request,
+    }
+
+    #[derive(serde::Serialize)]
+    enum State {
+        Failed { code: i32 },
+    }
+
+    mod bytes {
+        pub fn serialize<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.serialize_bytes(value)
+        }
+    }
+
+    fn values(entry: &crate::entry::Entry) -> Vec<(&str, &[u8])> {
+        entry
+            .values()
+            .map(|(field, value)| (field.as_str(), value))
+            .collect()
+    }
+
+    #[test]
+    fn test_to_entry() {
+        let event = Event {
+            message: "Hello",
+            count: 3,
+            ok: true,
+            ratio: 0.5,
+            missing: None,
+            tags: vec!["a", "b"],
+            user: User {
+                id: 7,
+                email: Some("a@example.com".to_owned()),
+            },
+            kind: Kind::Request,
+            state: State::Failed { code: -1 },
+            raw: b"\xff\n",
+        };
+
+        assert_eq!(
+            values(&to_entry(&event).unwrap()),
+            [
+                ("MESSAGE", b"Hello".as_slice()),
+                ("COUNT", b"3"),
+                ("OK", b"true"),
+                ("RATIO", b"0.5"),
+                ("TAGS", b"a"),
+                ("TAGS", b"b"),
+                ("USER__ID", b"7"),
+                ("USER__EMAILADDRESS", b"a@example.com"),
+                ("KIND", b"Request"),
+                ("STATE__FAILED__CODE", b"-1"),
+                ("RAW", b"\xff\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_and_prefix() {
+        let ids = std::collections::BTreeMap::from([(1, "one"), (2, "two")]);
+        let map = std::collections::BTreeMap::from([("ids", ids)]);
+        let mut serializer = Serializer::with_prefix(crate::raw::Field::validate("APP_").unwrap());
+        serializer.serialize(&map).unwrap();
+        assert_eq!(
+            values(serializer.entry()),
+            [("APP_IDS__1", b"one".as_slice()), ("APP_IDS__2", b"two")]
+        );
+
+        let wide = std::collections::BTreeMap::from([(-1i128, "signed"), (1 << 100, "large")]);
+        let map = std::collections::BTreeMap::from([("ids", wide)]);
+        assert_eq!(
+            values(&to_entry(&map).unwrap()),
+            [
+                ("IDS___1", b"signed".as_slice()),
+                ("IDS__1267650600228229401496703205376", b"large"),
+            ]
+        );
+        let wide = std::collections::BTreeMap::from([(u128::MAX, "max")]);
+        let map = std::collections::BTreeMap::from([("ids", wide)]);
+        assert_eq!(
+            values(&to_entry(&map).unwrap()),
+            [(
+                "IDS__340282366920938463463374607431768211455",
+                b"max".as_slice()
+            )]
+        );
+
+        let mut serializer = Serializer::with_prefix(crate::raw::Field::validate("APP_").unwrap());
+        assert_eq!(
+            serializer.serialize(&std::collections::BTreeMap::from([("_", 1)])),
+            Err(Error::InvalidKey("_".to_owned()))
+        );
+        assert_eq!(
+            serializer.serialize(&std::collections::BTreeMap::from([(1, 1)])),
+            Err(Error::InvalidKey("1".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_collisions() {
+        let map = std::collections::BTreeMap::from([
+            ("user-id", vec![1, 2]),
+            ("user.id", vec![3]),
+            ("user_id", vec![4, 5]),
+        ]);
+        assert_eq!(
+            values(&to_entry(&map).unwrap()),
+            [
+                ("USER_ID", b"1".as_slice()),
+                ("USER_ID", b"2"),
+                ("USER__ID", b"3"),
+                ("USER_ID_2", b"4"),
+                ("USER_ID_2", b"5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(to_entry("scalar"), Err(Error::MissingKey));
+        assert_eq!(to_entry(&[1, 2]), Err(Error::MissingKey));
+        assert_eq!(
+            to_entry(&std::collections::BTreeMap::from([("_", 1)])),
+            Err(Error::InvalidKey("_".to_owned()))
+        );
+        assert_eq!(
+            to_entry(&std::collections::BTreeMap::from([((1, 2), 1)])),
+            Err(Error::KeyNotScalar)
+        );
+    }
+}
@@ -1,31 +1,398 @@
-fn as_priority(level: slog::Level) -> crate::raw::Priority {
-    match level {
-        slog::Level::Critical => crate::raw::Priority::Critical,
-        slog::Level::Error => crate::raw::Priority::Error,
-        slog::Level::Warning => crate::raw::Priority::Warning,
-        slog::Level::Info => crate::raw::Priority::Info,
-        slog::Level::Debug => crate::raw::Priority::Debug,
-        slog::Level::Trace => crate::raw::Priority::Debug,
+impl std::convert::From<slog::Level> for crate::raw::Priority {
+    fn from(level: slog::Level) -> Self {
+        match level {
+            slog::Level::Critical => crate::raw::Priority::Critical,
+            slog::Level::Error => crate::raw::Priority::Error,
+            slog::Level::Warning => crate::raw::Priority::Warning,
+            slog::Level::Info => crate::raw::Priority::Info,
+            slog::Level::Debug => crate::raw::Priority::Debug,
+            slog::Level::Trace => crate::raw::Priority::Debug,
+        }
+    }
+}
+
+/// Maps a priority onto the closest level, with everything more severe than critical treated as
+/// [[`slog::Level::Critical`]].
+impl std::convert::From<crate::raw::Priority> for slog::Level {
+    fn from(priority: crate::raw::Priority) -> Self {
+        match priority {
+            crate::raw::Priority::Emergency
+            | crate::raw::Priority::Alert
+            | crate::raw::Priority::Critical => slog::Level::Critical,
+            crate::raw::Priority::Error => slog::Level::Error,
+            crate::raw::Priority::Warning => slog::Level::Warning,
+            crate::raw::Priority::Notice | crate::raw::Priority::Info => slog::Level::Info,
+            crate::raw::Priority::Debug => slog::Level::Debug,
+        }
     }
 }
 
 const MODULE_PATH: crate::raw::Field = crate::raw::Field::unchecked("MODULE_PATH");
+const LOG_LEVEL: crate::raw::Field = crate::raw::Field::unchecked("LOG_LEVEL");
+const KV_PREFIX: crate::raw::Field = crate::raw::Field::unchecked("KV_");
+const KV_CACHE_CAPACITY: usize = 64;
+
+/// Fields written for every record, which key-values are not allowed to replace.
+const RESERVED: [crate::raw::Field; 7] = [
+    crate::raw::CODE_FILE,
+    crate::raw::CODE_LINE,
+    crate::raw::CODE_FUNC,
+    crate::raw::MESSAGE,
+    crate::raw::PRIORITY,
+    LOG_LEVEL,
+    MODULE_PATH,
+];
+
+/// PriorityMap decides the priority that records of each level are sent with, or whether they're
+/// sent at all.
+///
+/// By default levels are mapped as by `From<slog::Level>`, which sends both `Debug` and `Trace`
+/// as [[`crate::raw::Priority::Debug`]].  The level is always written to the `LOG_LEVEL` field,
+/// so the two can still be told apart.
+pub type PriorityMap = crate::raw::PriorityMap<slog::Level>;
+
+impl crate::raw::Level for slog::Level {
+    const ALL: &'static [Self] = &[
+        slog::Level::Critical,
+        slog::Level::Error,
+        slog::Level::Warning,
+        slog::Level::Info,
+        slog::Level::Debug,
+        slog::Level::Trace,
+    ];
+
+    fn index(self) -> usize {
+        // Levels start from Critical at 1.
+        self.as_usize() - 1
+    }
+}
 
+type Fields = Vec<(crate::raw::OwnedField, Vec<u8>)>;
+
+/// Serializer converts key-values into fields, keeping the type of each value in mind.
+///
+/// Keys are sanitized by [[`crate::raw::OwnedField::sanitize`]], or prefixed by
+/// [[`crate::raw::OwnedField::sanitize_with_prefix`]] when a prefix is configured.  Keys that
+/// would replace a field written for every record, such as `MESSAGE` or `PRIORITY`, are prefixed
+/// with `KV_` instead.
+///
+/// When keys are flattened, they're named by [[`crate::flatten::FieldNames`]] instead, which also
+/// carries the prefix.  The fields written for every record are reserved, so keys that share a
+/// name with one of them or with another key are renamed or dropped, e.g. `message` is written as
+/// `MESSAGE_2`.
 #[derive(Debug, Default)]
-struct Serializer {
-    fields: Vec<(crate::raw::OwnedField, String)>,
+struct Serializer<'a> {
+    prefix: Option<&'a crate::raw::OwnedField>,
+    names: Option<crate::flatten::FieldNames>,
+    fields: Fields,
 }
 
-impl slog::Serializer for Serializer {
-    fn emit_arguments(&mut self, key: slog::Key, val: &std::fmt::Arguments<'_>) -> slog::Result {
-        if let Some(field) = crate::raw::OwnedField::sanitize(key) {
-            self.fields.push((field, format!("{}", val)));
+impl<'a> Serializer<'a> {
+    fn new(prefix: Option<&'a crate::raw::OwnedField>) -> Self {
+        Self {
+            prefix,
+            names: None,
+            fields: Vec::new(),
+        }
+    }
+
+    fn flattened(mut names: crate::flatten::FieldNames) -> Self {
+        for field in RESERVED {
+            names.reserve(field);
+        }
+        Self {
+            prefix: None,
+            names: Some(names),
+            fields: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, key: &str, value: Vec<u8>) {
+        let field = match (&mut self.names, self.prefix) {
+            (Some(names), _) => names.name(key),
+            (None, Some(prefix)) => Some(crate::raw::OwnedField::sanitize_with_prefix(
+                key,
+                prefix.into(),
+            )),
+            (None, None) => crate::raw::OwnedField::sanitize(key).map(|field| {
+                if RESERVED.contains(&(&field).into()) {
+                    crate::raw::OwnedField::sanitize_with_prefix(field.as_str(), KV_PREFIX)
+                } else {
+                    field
+                }
+            }),
+        };
+
+        if let Some(field) = field {
+            self.fields.push((field, value));
         }
+    }
+}
+
+macro_rules! emit_to_string {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $name(&mut self, key: slog::Key, val: $ty) -> slog::Result {
+                self.push(key, val.to_string().into_bytes());
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl slog::Serializer for Serializer<'_> {
+    emit_to_string!(
+        emit_usize: usize,
+        emit_isize: isize,
+        emit_char: char,
+        emit_u8: u8,
+        emit_i8: i8,
+        emit_u16: u16,
+        emit_i16: i16,
+        emit_u32: u32,
+        emit_i32: i32,
+        emit_u64: u64,
+        emit_i64: i64,
+        emit_u128: u128,
+        emit_i128: i128,
+        emit_f32: f32,
+        emit_f64: f64,
+    );
+
+    fn emit_bool(&mut self, key: slog::Key, val: bool) -> slog::Result {
+        self.push(key, if val { "true" } else { "false" }.into());
+        Ok(())
+    }
+
+    fn emit_str(&mut self, key: slog::Key, val: &str) -> slog::Result {
+        self.push(key, val.into());
+        Ok(())
+    }
+
+    /// A unit value is kept as a field with an empty value, so its presence can be matched.
+    fn emit_unit(&mut self, key: slog::Key) -> slog::Result {
+        self.push(key, Vec::new());
+        Ok(())
+    }
+
+    /// A missing value is left out entirely.
+    fn emit_none(&mut self, _key: slog::Key) -> slog::Result {
+        Ok(())
+    }
+
+    /// Bytes are stored as is, rather than as hex, since the journal allows binary values.
+    fn emit_bytes(&mut self, key: slog::Key, bytes: &[u8], _kind: slog::BytesKind) -> slog::Result {
+        self.push(key, bytes.to_vec());
+        Ok(())
+    }
+
+    /// An error and its sources are written as described in [[`crate::error`]].
+    fn emit_error(
+        &mut self,
+        key: slog::Key,
+        error: &(dyn std::error::Error + 'static),
+    ) -> slog::Result {
+        for (part, value) in crate::error::parts(error) {
+            match part.key(key) {
+                Some(key) => self.push(&key, value.into_bytes()),
+                None => self
+                    .fields
+                    .push((crate::raw::ERRNO.into(), value.into_bytes())),
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, key: slog::Key, val: &std::fmt::Arguments<'_>) -> slog::Result {
+        let value = val
+            .as_str()
+            .map_or_else(|| val.to_string(), ToOwned::to_owned);
+        self.push(key, value.into_bytes());
         Ok(())
     }
 }
 
-impl slog::Drain for crate::raw::JournalWriter {
+/// The address of the node that a list shares with its clones, which identifies its key-values.
+fn list_id(list: &slog::OwnedKVList) -> usize {
+    type Node = std::sync::Arc<dyn slog::SendSyncRefUnwindSafeKV>;
+    // OwnedKVList holds nothing but the `Arc` of its node, which it doesn't expose.
+    const _: () = assert!(std::mem::size_of::<slog::OwnedKVList>() == std::mem::size_of::<Node>());
+    // SAFETY: the list is a single `Arc` field, so it has the same layout as the `Arc`.
+    let node = unsafe { &*(list as *const slog::OwnedKVList).cast::<Node>() };
+    std::sync::Arc::as_ptr(node) as *const () as usize
+}
+
+/// KvCache keeps the fields of the key-values of recently used loggers, so that they're
+/// serialized once per logger rather than once per record.
+///
+/// Entries are found by [[`list_id`]], and hold a clone of their list so that its node can't be
+/// freed and its address reused by another list while it's cached.
+#[derive(Debug, Default)]
+struct KvCache {
+    entries: std::sync::Mutex<
+        std::collections::HashMap<usize, (slog::OwnedKVList, std::sync::Arc<Fields>)>,
+    >,
+}
+
+impl KvCache {
+    fn get<F>(&self, list: &slog::OwnedKVList, serialize: F) -> slog::Result<std::sync::Arc<Fields>>
+    where
+        F: FnOnce() -> slog::Result<Fields>,
+    {
+        let id = list_id(list);
+        if let Some((_, fields)) = self.lock().get(&id) {
+            return Ok(fields.clone());
+        }
+
+        let fields = std::sync::Arc::new(serialize()?);
+        let mut entries = self.lock();
+        if entries.len() >= KV_CACHE_CAPACITY {
+            entries.clear();
+        }
+        entries.insert(id, (list.clone(), fields.clone()));
+        Ok(fields)
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<
+        '_,
+        std::collections::HashMap<usize, (slog::OwnedKVList, std::sync::Arc<Fields>)>,
+    > {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// JournalDrain is a [[`slog::Drain`]] that sends records to the journal.
+///
+/// ```no_run
+/// let drain = journaled::slog::JournalDrain::new()
+///     .unwrap()
+///     .with_level(slog::Level::Debug)
+///     .with_key_prefix(journaled::raw::Field::validate("APP_").unwrap());
+/// let logger = slog::Logger::root(slog::Drain::fuse(drain), slog::o!("version" => "1.0"));
+///
+/// slog::info!(logger, "started"; "port" => 8080);
+/// ```
+#[derive(Debug)]
+pub struct JournalDrain {
+    writer: crate::raw::JournalWriter,
+    level: slog::Level,
+    priorities: PriorityMap,
+    key_prefix: Option<crate::raw::OwnedField>,
+    flatten_keys: Option<crate::flatten::Collision>,
+    cache: Option<KvCache>,
+}
+
+impl JournalDrain {
+    pub fn new() -> std::io::Result<Self> {
+        crate::raw::JournalWriter::new().map(Self::from_writer)
+    }
+
+    /// Uses an already configured writer, e.g. one with a facility.
+    pub fn from_writer(writer: crate::raw::JournalWriter) -> Self {
+        Self {
+            writer,
+            level: slog::Level::Trace,
+            priorities: PriorityMap::default(),
+            key_prefix: None,
+            flatten_keys: None,
+            cache: None,
+        }
+    }
+
+    /// Only sends records that are at least as severe as `level`.
+    pub fn with_level(mut self, level: slog::Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Changes the priority that each level is sent with, e.g. to send `Info` as `Notice`.
+    pub fn with_priorities(mut self, priorities: PriorityMap) -> Self {
+        self.priorities = priorities;
+        self
+    }
+
+    /// Prefixes the field names of key-values, e.g. with a prefix of `APP_` the key `user_id` is
+    /// written as `APP_USER_ID`.
+    pub fn with_key_prefix(mut self, prefix: crate::raw::Field) -> Self {
+        self.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Flattens nested keys as described in [[`crate::flatten`]], e.g. `http.status` is written
+    /// as `HTTP__STATUS`, and resolves keys that share a name within a record by `collision`.
+    ///
+    /// The logger's key-values claim their names before the record's.
+    pub fn with_flatten_keys(mut self, collision: crate::flatten::Collision) -> Self {
+        self.flatten_keys = Some(collision);
+        self
+    }
+
+    /// Serializes the key-values of each logger once, for its first record, and reuses their
+    /// fields for the records after it, rather than serializing them for every record.
+    ///
+    /// Lazy values, such as [[`slog::FnValue`]], are then evaluated only for the first record, so
+    /// loggers whose values change from record to record should leave this off.
+    pub fn with_cached_logger_values(mut self) -> Self {
+        self.cache = Some(KvCache::default());
+        self
+    }
+
+    /// Sends any pending summaries, as by [[`crate::raw::JournalWriter::flush`]].  The drain can
+    /// be kept in an `Arc` to still be reachable once it's given to a logger.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn serializer(&self) -> Serializer<'_> {
+        match self.flatten_keys {
+            Some(collision) => {
+                let names = crate::flatten::FieldNames::new(collision);
+                Serializer::flattened(match &self.key_prefix {
+                    Some(prefix) => names.with_prefix(prefix.into()),
+                    None => names,
+                })
+            }
+            None => Serializer::new(self.key_prefix.as_ref()),
+        }
+    }
+
+    /// The fields of the logger's key-values and of the record's.  The logger's are taken from
+    /// the cache if it's enabled, and otherwise serialized for every record.
+    fn fields(
+        &self,
+        record: &slog::Record<'_>,
+        logger_kv: &slog::OwnedKVList,
+    ) -> slog::Result<(std::sync::Arc<Fields>, Fields)> {
+        use slog::KV;
+
+        let mut serializer = self.serializer();
+        let logger_fields = match &self.cache {
+            Some(cache) => {
+                let fields = cache.get(logger_kv, || {
+                    let mut serializer = self.serializer();
+                    logger_kv.serialize(record, &mut serializer)?;
+                    Ok(serializer.fields)
+                })?;
+                if let Some(names) = &mut serializer.names {
+                    for (field, _) in fields.iter() {
+                        names.reserve(field);
+                    }
+                }
+                fields
+            }
+            None => {
+                logger_kv.serialize(record, &mut serializer)?;
+                std::sync::Arc::new(std::mem::take(&mut serializer.fields))
+            }
+        };
+        record.kv().serialize(record, &mut serializer)?;
+        Ok((logger_fields, serializer.fields))
+    }
+}
+
+impl slog::Drain for JournalDrain {
     type Ok = ();
     type Err = std::io::Error;
 
@@ -34,24 +401,37 @@ impl slog::Drain for crate::raw::JournalWriter {
         record: &slog::Record<'_>,
         logger_kv: &slog::OwnedKVList,
     ) -> Result<Self::Ok, Self::Err> {
-        if !self.is_enabled(record.level()) {
-            return Ok(());
-        }
+        let priority = match self.priorities.get(record.level()) {
+            Some(priority) if record.level().is_at_least(self.level) => priority,
+            _ => return Ok(()),
+        };
 
-        let line = record.line().to_string();
-        let msg = record.msg().as_str().map_or_else(
-            || record.msg().to_string().into(),
-            std::borrow::Cow::Borrowed,
-        );
+        let (logger_fields, record_fields) = self.fields(record, logger_kv)?;
+        send(
+            &self.writer,
+            priority,
+            record,
+            logger_fields.iter().chain(record_fields.iter()),
+        )
+    }
 
-        let values = [
-            (crate::raw::CODE_FILE, record.file()),
-            (crate::raw::CODE_LINE, &line),
-            (crate::raw::MESSAGE, &msg),
-            as_priority(record.level()).as_value(),
-            (MODULE_PATH, record.module()),
-        ];
+    fn is_enabled(&self, level: slog::Level) -> bool {
+        level.is_at_least(self.level) && self.priorities.get(level).is_some()
+    }
+}
 
+/// Sends every record, at the priority its level converts to.  Records aren't filtered by level
+/// and keys aren't prefixed or flattened; use a [[`JournalDrain`]], or wrap the writer in
+/// [[`slog::LevelFilter`]], for that.
+impl slog::Drain for crate::raw::JournalWriter {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(
+        &self,
+        record: &slog::Record<'_>,
+        logger_kv: &slog::OwnedKVList,
+    ) -> Result<Self::Ok, Self::Err> {
         let serializer = {
             use slog::KV;
 
@@ -61,13 +441,303 @@ impl slog::Drain for crate::raw::JournalWriter {
             serializer
         };
 
-        self.send(
-            values.into_iter().chain(
-                serializer
-                    .fields
-                    .iter()
-                    .map(|(k, v)| (k.into(), v.as_str())),
-            ),
+        send(
+            self,
+            record.level().into(),
+            record,
+            serializer.fields.iter(),
         )
     }
 }
+
+fn send<'a, I>(
+    writer: &crate::raw::JournalWriter,
+    priority: crate::raw::Priority,
+    record: &slog::Record<'_>,
+    fields: I,
+) -> std::io::Result<()>
+where
+    I: Iterator<Item = &'a (crate::raw::OwnedField, Vec<u8>)> + Clone,
+{
+    let line = record.line().to_string();
+    let msg = record.msg().as_str().map_or_else(
+        || record.msg().to_string().into(),
+        std::borrow::Cow::Borrowed,
+    );
+
+    let values = [
+        (crate::raw::CODE_FILE, record.file()),
+        (crate::raw::CODE_LINE, &line),
+        (crate::raw::MESSAGE, &msg),
+        priority.as_value(),
+        (LOG_LEVEL, record.level().as_str()),
+        (MODULE_PATH, record.module()),
+    ];
+
+    // slog's macros leave the function empty, but it may be set when a record is built by hand.
+    let func = Some(record.function())
+        .filter(|func| !func.is_empty())
+        .map(|func| (crate::raw::CODE_FUNC, func));
+
+    writer.send_bytes(
+        values
+            .into_iter()
+            .chain(func)
+            .map(|(k, v)| (k, v.as_bytes()))
+            .chain(
+                fields
+                    .filter(crate::error::first_errno())
+                    .map(|(k, v)| (k.into(), v.as_slice())),
+            ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_record<F>(f: F)
+    where
+        F: FnOnce(&slog::Record),
+    {
+        let record_static = slog::record_static!(slog::Level::Info, "");
+        f(&slog::Record::new(
+            &record_static,
+            &format_args!(""),
+            slog::b!(),
+        ))
+    }
+
+    fn fields(fields: &Fields) -> Vec<(&str, &[u8])> {
+        fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_slice()))
+            .collect()
+    }
+
+    #[test]
+    fn test_serializer() {
+        use slog::KV;
+
+        let error = std::io::Error::other("inner");
+        let kv = slog::b!(
+            "count" => 3u64,
+            "ratio" => 0.5,
+            "enabled" => true,
+            "name" => "test",
+            "missing" => None::<u8>,
+            "unit" => (),
+            "message" => "collides",
+            "error" => slog::ErrorRef(&error),
+        );
+        let mut serializer = Serializer::default();
+        with_record(|record| kv.serialize(record, &mut serializer).unwrap());
+        let mut expected: Vec<(&str, &[u8])> = vec![
+            ("COUNT", b"3"),
+            ("RATIO", b"0.5"),
+            ("ENABLED", b"true"),
+            ("NAME", b"test"),
+            ("UNIT", b""),
+            ("KV_MESSAGE", b"collides"),
+            ("ERROR", b"inner"),
+        ];
+        // slog serializes the most recently added key-values first.
+        expected.reverse();
+        assert_eq!(fields(&serializer.fields), expected);
+    }
+
+    #[test]
+    fn test_serializer_prefix() {
+        use slog::Serializer as _;
+
+        let prefix = crate::raw::Field::validate("APP_").unwrap().into();
+        let mut serializer = Serializer::new(Some(&prefix));
+        serializer
+            .emit_bytes("raw", b"\0\xff", slog::BytesKind::Stream)
+            .unwrap();
+        serializer.emit_str("message", "ok").unwrap();
+        assert_eq!(
+            fields(&serializer.fields),
+            [("APP_RAW", &b"\0\xff"[..]), ("APP_MESSAGE", b"ok")]
+        );
+    }
+
+    #[test]
+    fn test_serializer_flattened() {
+        use slog::Serializer as _;
+
+        let mut serializer = Serializer::flattened(crate::flatten::FieldNames::new(
+            crate::flatten::Collision::Suffix,
+        ));
+        serializer.emit_u16("http.status", 200).unwrap();
+        serializer.emit_u16("http::status", 201).unwrap();
+        serializer.emit_u16("http_status", 202).unwrap();
+        serializer.emit_str("message", "ok").unwrap();
+        assert_eq!(
+            fields(&serializer.fields),
+            [
+                ("HTTP__STATUS", &b"200"[..]),
+                ("HTTP__STATUS_2", b"201"),
+                ("HTTP_STATUS", b"202"),
+                ("MESSAGE_2", b"ok"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drain_fields() {
+        let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let logger_kv = {
+            let counter = counter.clone();
+            slog::OwnedKVList::from(slog::o!(
+                "http.status" => 1,
+                "dynamic" => slog::FnValue(move |_| {
+                    counter.load(std::sync::atomic::Ordering::SeqCst)
+                }),
+            ))
+        };
+        let drain = JournalDrain::new()
+            .unwrap()
+            .with_flatten_keys(crate::flatten::Collision::Suffix);
+        let record_static = slog::record_static!(slog::Level::Info, "");
+        let fields_of = || {
+            let (logger_fields, record_fields) = drain
+                .fields(
+                    &slog::Record::new(
+                        &record_static,
+                        &format_args!(""),
+                        slog::b!("http.status" => 2),
+                    ),
+                    &logger_kv,
+                )
+                .unwrap();
+            [logger_fields.as_slice(), &record_fields].concat()
+        };
+
+        let first = fields_of();
+        assert_eq!(
+            fields(&first),
+            [
+                ("DYNAMIC", &b"0"[..]),
+                ("HTTP__STATUS", b"1"),
+                ("HTTP__STATUS_2", b"2"),
+            ]
+        );
+
+        // Lazy values are evaluated again for each record.
+        counter.store(1, std::sync::atomic::Ordering::SeqCst);
+        let second = fields_of();
+        assert_eq!(fields(&second)[0], ("DYNAMIC", &b"1"[..]));
+    }
+
+    /// OsError is a value that's serialized as the OS error with its code.
+    struct OsError(i32);
+
+    impl slog::Value for OsError {
+        fn serialize(
+            &self,
+            _record: &slog::Record<'_>,
+            key: slog::Key,
+            serializer: &mut dyn slog::Serializer,
+        ) -> slog::Result {
+            serializer.emit_error(key, &std::io::Error::from_raw_os_error(self.0))
+        }
+    }
+
+    #[test]
+    fn test_drain_errno() {
+        let logger_kv = slog::OwnedKVList::from(slog::o!("error" => OsError(2)));
+        let drain = JournalDrain::new()
+            .unwrap()
+            .with_key_prefix(crate::raw::Field::validate("APP_").unwrap())
+            .with_flatten_keys(crate::flatten::Collision::Suffix);
+        let record_static = slog::record_static!(slog::Level::Info, "");
+        let (logger_fields, record_fields) = drain
+            .fields(
+                &slog::Record::new(
+                    &record_static,
+                    &format_args!(""),
+                    slog::b!("cause" => OsError(13)),
+                ),
+                &logger_kv,
+            )
+            .unwrap();
+
+        // The logger's error comes first, so its code is kept, and neither is prefixed.
+        let entry: Vec<_> = logger_fields
+            .iter()
+            .chain(record_fields.iter())
+            .filter(crate::error::first_errno())
+            .collect();
+        let keys: Vec<_> = entry.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["APP_ERROR", "ERRNO", "APP_CAUSE"]);
+        assert_eq!(entry[1].1, b"2");
+    }
+
+    /// Counted is a value that counts how often it's serialized.
+    struct Counted(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl slog::Value for Counted {
+        fn serialize(
+            &self,
+            _record: &slog::Record<'_>,
+            key: slog::Key,
+            serializer: &mut dyn slog::Serializer,
+        ) -> slog::Result {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            serializer.emit_str(key, "counted")
+        }
+    }
+
+    #[test]
+    fn test_cached_logger_values() {
+        let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let logger = slog::Logger::root(
+            slog::Discard,
+            slog::o!("http.status" => 1, "counted" => Counted(counter.clone())),
+        );
+        let child = logger.new(slog::o!("child" => true));
+        assert_eq!(list_id(logger.list()), list_id(logger.clone().list()));
+        assert_ne!(list_id(logger.list()), list_id(child.list()));
+
+        let drain = JournalDrain::new()
+            .unwrap()
+            .with_flatten_keys(crate::flatten::Collision::Suffix)
+            .with_cached_logger_values();
+        let record_static = slog::record_static!(slog::Level::Info, "");
+        let fields_of = |list: &slog::OwnedKVList| {
+            let (logger_fields, record_fields) = drain
+                .fields(
+                    &slog::Record::new(
+                        &record_static,
+                        &format_args!(""),
+                        slog::b!("http.status" => 2),
+                    ),
+                    list,
+                )
+                .unwrap();
+            [logger_fields.as_slice(), &record_fields].concat()
+        };
+
+        let first = fields_of(logger.list());
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(
+            fields(&first),
+            [
+                ("COUNTED", &b"counted"[..]),
+                ("HTTP__STATUS", b"1"),
+                ("HTTP__STATUS_2", b"2"),
+            ]
+        );
+
+        // The second record, and clones of the logger, reuse the logger's fields.
+        assert_eq!(fields_of(logger.list()), first);
+        assert_eq!(fields_of(logger.clone().list()), first);
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // A child logger has its own list, which is serialized once too.
+        fields_of(child.list());
+        fields_of(child.list());
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+}
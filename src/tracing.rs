@@ -0,0 +1,726 @@
+impl std::convert::From<tracing_core::Level> for crate::raw::Priority {
+    fn from(level: tracing_core::Level) -> Self {
+        match level {
+            tracing_core::Level::ERROR => crate::raw::Priority::Error,
+            tracing_core::Level::WARN => crate::raw::Priority::Warning,
+            tracing_core::Level::INFO => crate::raw::Priority::Info,
+            tracing_core::Level::DEBUG => crate::raw::Priority::Debug,
+            tracing_core::Level::TRACE => crate::raw::Priority::Debug,
+        }
+    }
+}
+
+/// Maps a priority onto the closest level, with everything more severe than an error treated as
+/// [[`tracing_core::Level::ERROR`]].
+impl std::convert::From<crate::raw::Priority> for tracing_core::Level {
+    fn from(priority: crate::raw::Priority) -> Self {
+        match priority {
+            crate::raw::Priority::Emergency
+            | crate::raw::Priority::Alert
+            | crate::raw::Priority::Critical
+            | crate::raw::Priority::Error => tracing_core::Level::ERROR,
+            crate::raw::Priority::Warning => tracing_core::Level::WARN,
+            crate::raw::Priority::Notice | crate::raw::Priority::Info => tracing_core::Level::INFO,
+            crate::raw::Priority::Debug => tracing_core::Level::DEBUG,
+        }
+    }
+}
+
+const TARGET: crate::raw::Field = crate::raw::Field::unchecked("TARGET");
+const MODULE_PATH: crate::raw::Field = crate::raw::Field::unchecked("MODULE_PATH");
+const LOG_LEVEL: crate::raw::Field = crate::raw::Field::unchecked("LOG_LEVEL");
+const SPAN_NAME: crate::raw::Field = crate::raw::Field::unchecked("SPAN_NAME");
+const SPAN_PATH: crate::raw::Field = crate::raw::Field::unchecked("SPAN_PATH");
+const SPAN_BUSY_USEC: crate::raw::Field = crate::raw::Field::unchecked("SPAN_BUSY_USEC");
+const SPAN_IDLE_USEC: crate::raw::Field = crate::raw::Field::unchecked("SPAN_IDLE_USEC");
+const KV_PREFIX: crate::raw::Field = crate::raw::Field::unchecked("KV_");
+
+/// The field of an event that holds its message, e.g. from `info!("started")`.
+const MESSAGE_KEY: &str = "message";
+/// The field of an event or span that names the function it came from, which is written to
+/// `CODE_FUNC`.
+const CODE_FUNC_KEY: &str = "code.func";
+/// Separates the span names in `SPAN_PATH`, e.g. `request:query`.
+const SPAN_PATH_SEPARATOR: &str = ":";
+
+crate::message_id! {
+    /// Identifies the entries sent when a span closes, if enabled by
+    /// [[`JournalLayer::with_span_close`]].
+    pub SPAN_CLOSED = "bfb327368b334be7b98588117c1f158c";
+}
+
+/// Fields written for every event, which user-defined fields are not allowed to replace.
+const RESERVED: [crate::raw::Field; 13] = [
+    crate::raw::PRIORITY,
+    crate::raw::MESSAGE,
+    LOG_LEVEL,
+    TARGET,
+    crate::raw::CODE_FILE,
+    crate::raw::CODE_LINE,
+    crate::raw::CODE_FUNC,
+    MODULE_PATH,
+    SPAN_NAME,
+    SPAN_PATH,
+    SPAN_BUSY_USEC,
+    SPAN_IDLE_USEC,
+    crate::raw::MESSAGE_ID,
+];
+
+type Fields = Vec<(crate::raw::OwnedField, Vec<u8>)>;
+
+/// Visitor converts the fields of an event or span into journal fields.
+///
+/// Keys are sanitized by [[`crate::raw::OwnedField::sanitize`]], or prefixed by
+/// [[`crate::raw::OwnedField::sanitize_with_prefix`]] when a prefix is configured.  Keys that
+/// would replace a field written for every event, such as `MESSAGE` or `PRIORITY`, are prefixed
+/// with `KV_` instead.  The `message` of an event is kept aside to be sent as `MESSAGE`, and
+/// `code.func` to be sent as `CODE_FUNC`.
+#[derive(Debug, Default)]
+struct Visitor<'a> {
+    prefix: Option<&'a crate::raw::OwnedField>,
+    message: Option<Vec<u8>>,
+    func: Option<Vec<u8>>,
+    fields: Fields,
+}
+
+impl<'a> Visitor<'a> {
+    fn new(prefix: Option<&'a crate::raw::OwnedField>) -> Self {
+        Self {
+            prefix,
+            message: None,
+            func: None,
+            fields: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, key: &str, value: Vec<u8>) {
+        let field = match self.prefix {
+            Some(prefix) => Some(crate::raw::OwnedField::sanitize_with_prefix(
+                key,
+                prefix.into(),
+            )),
+            None => crate::raw::OwnedField::sanitize(key),
+        };
+
+        if let Some(field) = field {
+            let field = if RESERVED.contains(&(&field).into()) {
+                crate::raw::OwnedField::sanitize_with_prefix(field.as_str(), KV_PREFIX)
+            } else {
+                field
+            };
+            self.fields.push((field, value));
+        }
+    }
+
+    fn push_error(&mut self, key: &str, error: &(dyn std::error::Error + 'static)) {
+        for (part, value) in crate::error::parts(error) {
+            match part.key(key) {
+                Some(key) => self.push(&key, value.into_bytes()),
+                None => self
+                    .fields
+                    .push((crate::raw::ERRNO.into(), value.into_bytes())),
+            }
+        }
+    }
+
+    /// Records a value, keeping the message of an event and the function aside.
+    fn record(&mut self, field: &tracing_core::Field, value: Vec<u8>, event: bool) {
+        match field.name() {
+            MESSAGE_KEY if event => self.message = Some(value),
+            CODE_FUNC_KEY => self.func = Some(value),
+            name => self.push(name, value),
+        }
+    }
+}
+
+/// EventVisitor records the fields of an event, including its message.
+struct EventVisitor<'v, 'a>(&'v mut Visitor<'a>);
+
+/// SpanVisitor records the fields of a span, where `message` is an ordinary key.
+struct SpanVisitor<'v, 'a>(&'v mut Visitor<'a>);
+
+macro_rules! visit {
+    ($visitor:ident, $event:expr) => {
+        impl tracing_core::field::Visit for $visitor<'_, '_> {
+            fn record_f64(&mut self, field: &tracing_core::Field, value: f64) {
+                self.0.record(field, value.to_string().into_bytes(), $event);
+            }
+
+            fn record_i64(&mut self, field: &tracing_core::Field, value: i64) {
+                self.0.record(field, value.to_string().into_bytes(), $event);
+            }
+
+            fn record_u64(&mut self, field: &tracing_core::Field, value: u64) {
+                self.0.record(field, value.to_string().into_bytes(), $event);
+            }
+
+            fn record_i128(&mut self, field: &tracing_core::Field, value: i128) {
+                self.0.record(field, value.to_string().into_bytes(), $event);
+            }
+
+            fn record_u128(&mut self, field: &tracing_core::Field, value: u128) {
+                self.0.record(field, value.to_string().into_bytes(), $event);
+            }
+
+            fn record_bool(&mut self, field: &tracing_core::Field, value: bool) {
+                self.0.record(field, value.to_string().into_bytes(), $event);
+            }
+
+            fn record_str(&mut self, field: &tracing_core::Field, value: &str) {
+                self.0.record(field, value.into(), $event);
+            }
+
+            /// Bytes are stored as is, since the journal allows binary values.
+            fn record_bytes(&mut self, field: &tracing_core::Field, value: &[u8]) {
+                self.0.record(field, value.to_vec(), $event);
+            }
+
+            /// An error and its sources are written as described in [[`crate::error`]].
+            fn record_error(
+                &mut self,
+                field: &tracing_core::Field,
+                value: &(dyn std::error::Error + 'static),
+            ) {
+                self.0.push_error(field.name(), value);
+            }
+
+            fn record_debug(&mut self, field: &tracing_core::Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .record(field, format!("{:?}", value).into_bytes(), $event);
+            }
+        }
+    };
+}
+
+visit!(EventVisitor, true);
+visit!(SpanVisitor, false);
+
+/// SpanFields are the converted fields of a span, stored in its extensions.
+#[derive(Debug, Default)]
+struct SpanFields {
+    fields: Fields,
+    func: Option<Vec<u8>>,
+}
+
+impl SpanFields {
+    fn new(visitor: Visitor<'_>) -> Self {
+        Self {
+            fields: visitor.fields,
+            func: visitor.func,
+        }
+    }
+
+    /// Adds recorded values, replacing the earlier values of the same fields.
+    fn update(&mut self, visitor: Visitor<'_>) {
+        let fields = visitor.fields;
+        self.fields
+            .retain(|(field, _)| !fields.iter().any(|(recorded, _)| recorded == field));
+        self.fields.extend(fields);
+        if visitor.func.is_some() {
+            self.func = visitor.func;
+        }
+    }
+}
+
+/// Timings track how long a span has been entered, and how long it's been idle in between.
+#[derive(Debug)]
+struct Timings {
+    busy: std::time::Duration,
+    idle: std::time::Duration,
+    last: std::time::Instant,
+}
+
+impl Timings {
+    fn new() -> Self {
+        Self {
+            busy: std::time::Duration::ZERO,
+            idle: std::time::Duration::ZERO,
+            last: std::time::Instant::now(),
+        }
+    }
+
+    fn enter(&mut self) {
+        let now = std::time::Instant::now();
+        self.idle += now.saturating_duration_since(self.last);
+        self.last = now;
+    }
+
+    fn exit(&mut self) {
+        let now = std::time::Instant::now();
+        self.busy += now.saturating_duration_since(self.last);
+        self.last = now;
+    }
+
+    /// Counts the time since the span was last exited as idle.
+    fn close(&mut self) {
+        self.enter();
+    }
+}
+
+/// JournalLayer is a [[`tracing_subscriber::Layer`]] that sends events to the journal.
+///
+/// Each event is sent with its fields and the fields of the spans it's in, from the outermost
+/// span to the innermost.  The innermost span's name is written to `SPAN_NAME` and the names of
+/// all of the spans to `SPAN_PATH`, e.g. `request:query`.  tracing doesn't record the function
+/// that an event came from, so `CODE_FUNC` is only written from a `code.func` field of the event,
+/// or of the innermost span that has one, e.g. `#[instrument(fields(code.func = "accept"))]`.
+///
+/// Entries can also be sent when spans close, as enabled by [[`JournalLayer::with_span_close`]].
+///
+/// ```no_run
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let layer = journaled::tracing::JournalLayer::new()
+///     .unwrap()
+///     .with_field_prefix(journaled::raw::Field::validate("APP_").unwrap());
+/// let subscriber = tracing_subscriber::registry().with(layer);
+/// tracing::subscriber::set_global_default(subscriber).unwrap();
+///
+/// tracing::info!(port = 8080, "started");
+/// ```
+#[derive(Debug)]
+pub struct JournalLayer {
+    writer: crate::raw::JournalWriter,
+    field_prefix: Option<crate::raw::OwnedField>,
+    span_close: bool,
+}
+
+impl JournalLayer {
+    pub fn new() -> std::io::Result<Self> {
+        crate::raw::JournalWriter::new().map(Self::from_writer)
+    }
+
+    /// Uses an already configured writer, e.g. one with a facility.
+    pub fn from_writer(writer: crate::raw::JournalWriter) -> Self {
+        Self {
+            writer,
+            field_prefix: None,
+            span_close: false,
+        }
+    }
+
+    /// Prefixes the names of event and span fields, e.g. with a prefix of `APP_` the field
+    /// `user_id` is written as `APP_USER_ID`.
+    pub fn with_field_prefix(mut self, prefix: crate::raw::Field) -> Self {
+        self.field_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sends an entry when a span closes, with the time it was entered in `SPAN_BUSY_USEC` and
+    /// the time in between in `SPAN_IDLE_USEC`.
+    ///
+    /// The entries have a `MESSAGE_ID` of [[`SPAN_CLOSED`]] and the priority of the span's level,
+    /// and include the fields of the span and of the spans it's in, e.g. to find slow requests:
+    ///
+    /// ```text
+    /// journalctl -o json MESSAGE_ID=bfb327368b334be7b98588117c1f158c SPAN_NAME=request
+    /// ```
+    pub fn with_span_close(mut self, enabled: bool) -> Self {
+        self.span_close = enabled;
+        self
+    }
+
+    /// Sends any pending summaries, as by [[`crate::raw::JournalWriter::flush`]].  Once installed,
+    /// the layer can be found with `tracing::dispatcher::get_default` and `downcast_ref`.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Sends an entry with the fields of the spans, from the outermost to the innermost, followed
+    /// by `fields`.
+    fn send<S>(
+        &self,
+        metadata: &tracing_core::Metadata<'_>,
+        spans: &[tracing_subscriber::registry::SpanRef<'_, S>],
+        values: &[(crate::raw::Field<'_>, &[u8])],
+        fields: &Fields,
+    ) where
+        S: for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        let extensions: Vec<_> = spans.iter().map(|span| span.extensions()).collect();
+        let span_fields = extensions
+            .iter()
+            .filter_map(|extensions| extensions.get::<SpanFields>())
+            .flat_map(|fields| fields.fields.iter());
+
+        let line = metadata.line().map(|line| line.to_string());
+        let innermost = spans.last().map(|span| span.metadata());
+        let span_path = span_path(spans.iter().map(|span| span.name()));
+        let span_values = [
+            innermost.map(|span| (SPAN_NAME, span.name())),
+            span_path.as_deref().map(|path| (SPAN_PATH, path)),
+        ];
+
+        if let Err(err) = self.writer.send_bytes(
+            metadata_values(metadata, line.as_deref())
+                .chain(span_values.into_iter().flatten())
+                .map(|(k, v)| (k, v.as_bytes()))
+                .chain(values.iter().map(|(k, v)| (*k, *v)))
+                .chain(
+                    span_fields
+                        .chain(fields.iter())
+                        .filter(crate::error::first_errno())
+                        .map(|(k, v)| (k.into(), v.as_slice())),
+                ),
+        ) {
+            eprintln!("logging failed: {}", err);
+        }
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for JournalLayer
+where
+    S: tracing_core::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing_core::span::Attributes<'_>,
+        id: &tracing_core::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut visitor = Visitor::new(self.field_prefix.as_ref());
+        attrs.record(&mut SpanVisitor(&mut visitor));
+        let mut extensions = span.extensions_mut();
+        extensions.insert(SpanFields::new(visitor));
+        if self.span_close {
+            extensions.insert(Timings::new());
+        }
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing_core::span::Id,
+        values: &tracing_core::span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut visitor = Visitor::new(self.field_prefix.as_ref());
+        values.record(&mut SpanVisitor(&mut visitor));
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            fields.update(visitor);
+        }
+    }
+
+    fn on_event(
+        &self,
+        event: &tracing_core::Event<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut visitor = Visitor::new(self.field_prefix.as_ref());
+        event.record(&mut EventVisitor(&mut visitor));
+
+        let spans: Vec<_> = ctx
+            .event_scope(event)
+            .map(|scope| scope.from_root().collect())
+            .unwrap_or_default();
+        let func = visitor.func.take().or_else(|| {
+            spans.iter().rev().find_map(|span| {
+                let extensions = span.extensions();
+                extensions.get::<SpanFields>()?.func.clone()
+            })
+        });
+        let values: Vec<_> = [
+            visitor
+                .message
+                .as_deref()
+                .map(|message| (crate::raw::MESSAGE, message)),
+            func.as_deref().map(|func| (crate::raw::CODE_FUNC, func)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        self.send(event.metadata(), &spans, &values, &visitor.fields);
+    }
+
+    fn on_enter(
+        &self,
+        id: &tracing_core::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timings) = span.extensions_mut().get_mut::<Timings>() {
+                timings.enter();
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &tracing_core::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timings) = span.extensions_mut().get_mut::<Timings>() {
+                timings.exit();
+            }
+        }
+    }
+
+    fn on_close(&self, id: tracing_core::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+        let (busy, idle) = match span.extensions_mut().get_mut::<Timings>() {
+            Some(timings) => {
+                timings.close();
+                (timings.busy.as_micros(), timings.idle.as_micros())
+            }
+            None => return,
+        };
+
+        let func = span
+            .extensions()
+            .get::<SpanFields>()
+            .and_then(|fields| fields.func.clone());
+        let message = format!("{} closed", span.name());
+        let busy = busy.to_string();
+        let idle = idle.to_string();
+        let spans: Vec<_> = span.scope().from_root().collect();
+
+        let values: Vec<_> = [
+            Some((crate::raw::MESSAGE, message.as_bytes())),
+            Some((crate::raw::MESSAGE_ID, SPAN_CLOSED.as_str().as_bytes())),
+            Some((SPAN_BUSY_USEC, busy.as_bytes())),
+            Some((SPAN_IDLE_USEC, idle.as_bytes())),
+            func.as_deref().map(|func| (crate::raw::CODE_FUNC, func)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        self.send(span.metadata(), &spans, &values, &Fields::new());
+    }
+}
+
+/// The fields that describe where an event or span came from, and its priority.
+fn metadata_values<'a>(
+    metadata: &'a tracing_core::Metadata<'a>,
+    line: Option<&'a str>,
+) -> impl Iterator<Item = (crate::raw::Field<'static>, &'a str)> + Clone {
+    let priority = crate::raw::Priority::from(*metadata.level());
+    [
+        Some(priority.as_value()),
+        Some((LOG_LEVEL, metadata.level().as_str())),
+        Some((TARGET, metadata.target())),
+        metadata.file().map(|file| (crate::raw::CODE_FILE, file)),
+        line.map(|line| (crate::raw::CODE_LINE, line)),
+        metadata.module_path().map(|path| (MODULE_PATH, path)),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Joins the names of spans from the outermost to the innermost, if there are any.
+fn span_path<'a, I>(names: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let names: Vec<_> = names.into_iter().collect();
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(SPAN_PATH_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(fields: &Fields) -> Vec<(&str, &[u8])> {
+        fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_slice()))
+            .collect()
+    }
+
+    /// Records the fields of an event through a subscriber that does nothing else.
+    struct Recorder<F>(std::sync::Mutex<Option<F>>);
+
+    impl<F> tracing_core::Subscriber for Recorder<F>
+    where
+        F: FnOnce(&tracing_core::Event<'_>) + Send + 'static,
+    {
+        fn enabled(&self, _metadata: &tracing_core::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &tracing_core::span::Attributes<'_>) -> tracing_core::span::Id {
+            tracing_core::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &tracing_core::span::Id, _values: &tracing_core::span::Record<'_>) {
+        }
+
+        fn record_follows_from(
+            &self,
+            _span: &tracing_core::span::Id,
+            _follows: &tracing_core::span::Id,
+        ) {
+        }
+
+        fn event(&self, event: &tracing_core::Event<'_>) {
+            if let Some(f) = self.0.lock().unwrap().take() {
+                f(event);
+            }
+        }
+
+        fn enter(&self, _span: &tracing_core::span::Id) {}
+
+        fn exit(&self, _span: &tracing_core::span::Id) {}
+    }
+
+    fn with_event<F>(emit: impl FnOnce(), f: F)
+    where
+        F: FnOnce(&tracing_core::Event<'_>) + Send + 'static,
+    {
+        let recorder = Recorder(std::sync::Mutex::new(Some(f)));
+        ::tracing::subscriber::with_default(recorder, emit);
+    }
+
+    #[test]
+    fn test_event_visitor() {
+        with_event(
+            || {
+                let error = std::io::Error::other("inner");
+                ::tracing::info!(
+                    count = 3u64,
+                    ratio = 0.5,
+                    enabled = true,
+                    name = "test",
+                    http.status = 200,
+                    priority = 1,
+                    debug = ?Some(1),
+                    code.func = "accept",
+                    error = &error as &(dyn std::error::Error + 'static),
+                    "Hello {}",
+                    "World",
+                );
+            },
+            |event| {
+                let mut visitor = Visitor::default();
+                event.record(&mut EventVisitor(&mut visitor));
+                assert_eq!(visitor.message.as_deref(), Some(&b"Hello World"[..]));
+                assert_eq!(visitor.func.as_deref(), Some(&b"accept"[..]));
+                assert_eq!(
+                    fields(&visitor.fields),
+                    [
+                        ("COUNT", &b"3"[..]),
+                        ("RATIO", b"0.5"),
+                        ("ENABLED", b"true"),
+                        ("NAME", b"test"),
+                        ("HTTP_STATUS", b"200"),
+                        ("KV_PRIORITY", b"1"),
+                        ("DEBUG", b"Some(1)"),
+                        ("ERROR", b"inner"),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_span_visitor() {
+        with_event(
+            || ::tracing::info!(message = "text", user_id = 7),
+            |event| {
+                let prefix = crate::raw::Field::validate("APP_").unwrap().into();
+                let mut visitor = Visitor::new(Some(&prefix));
+                event.record(&mut SpanVisitor(&mut visitor));
+                assert_eq!(visitor.message, None);
+                assert_eq!(
+                    fields(&visitor.fields),
+                    [("APP_MESSAGE", &b"text"[..]), ("APP_USER_ID", b"7")]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_errno() {
+        with_event(
+            || {
+                let error = std::io::Error::from_raw_os_error(2);
+                let cause = std::io::Error::from_raw_os_error(13);
+                ::tracing::info!(
+                    error = &error as &(dyn std::error::Error + 'static),
+                    cause = &cause as &(dyn std::error::Error + 'static),
+                );
+            },
+            |event| {
+                let prefix = crate::raw::Field::validate("APP_").unwrap().into();
+                let mut visitor = Visitor::new(Some(&prefix));
+                event.record(&mut EventVisitor(&mut visitor));
+
+                // Only the code of the first error is kept, under the bare ERRNO.
+                let entry: Vec<_> = visitor
+                    .fields
+                    .iter()
+                    .filter(crate::error::first_errno())
+                    .collect();
+                let keys: Vec<_> = entry.iter().map(|(k, _)| k.as_str()).collect();
+                assert_eq!(keys, ["APP_ERROR", "ERRNO", "APP_CAUSE"]);
+                assert_eq!(entry[1].1, b"2");
+            },
+        );
+    }
+
+    #[test]
+    fn test_span_fields_update() {
+        let field = |name| crate::raw::OwnedField::sanitize(name).unwrap();
+        let mut span_fields = SpanFields::new(Visitor {
+            fields: vec![(field("A"), b"1".to_vec()), (field("B"), b"2".to_vec())],
+            func: Some(b"accept".to_vec()),
+            ..Visitor::default()
+        });
+        span_fields.update(Visitor {
+            fields: vec![(field("A"), b"3".to_vec())],
+            ..Visitor::default()
+        });
+        assert_eq!(fields(&span_fields.fields), [("B", &b"2"[..]), ("A", b"3")]);
+        assert_eq!(span_fields.func.as_deref(), Some(&b"accept"[..]));
+    }
+
+    #[test]
+    fn test_timings() {
+        let mut timings = Timings::new();
+        timings.enter();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        timings.exit();
+        let busy = timings.busy;
+        timings.close();
+        assert!(busy >= std::time::Duration::from_millis(2));
+        assert_eq!(timings.busy, busy);
+    }
+
+    #[test]
+    fn test_span_path() {
+        assert_eq!(span_path([]), None);
+        assert_eq!(
+            span_path(["request", "query"]).as_deref(),
+            Some("request:query")
+        );
+    }
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(
+            crate::raw::Priority::from(tracing_core::Level::WARN),
+            crate::raw::Priority::Warning
+        );
+        assert_eq!(
+            crate::raw::Priority::from(tracing_core::Level::TRACE),
+            crate::raw::Priority::Debug
+        );
+        assert_eq!(
+            tracing_core::Level::from(crate::raw::Priority::Critical),
+            tracing_core::Level::ERROR
+        );
+    }
+}
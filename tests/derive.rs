@@ -0,0 +1,58 @@
+#[cfg(feature = "derive")]
+#[test]
+fn test_write() {
+    use journaled::entry::JournalEntry;
+
+    #[derive(JournalEntry)]
+    #[journal(
+        message_id = "0f3a1cf4d5cb4f2ba3de0e8f6b39c0a1",
+        priority = "warning",
+        message = "Request to {path} failed with {status}"
+    )]
+    struct RequestFailed<'a> {
+        path: &'a str,
+        status: u16,
+        #[journal(name = "REQUEST_ID")]
+        id: Option<u64>,
+        #[journal(skip)]
+        #[allow(dead_code)]
+        body: Vec<u8>,
+    }
+
+    let request = RequestFailed {
+        path: "/index.html",
+        status: 503,
+        id: Some(12),
+        body: b"unsent".to_vec(),
+    };
+    let entry = request.to_entry();
+    assert_eq!(
+        entry
+            .values()
+            .map(|(field, value)| (field.as_str(), value))
+            .collect::<Vec<_>>(),
+        [
+            ("MESSAGE_ID", b"0f3a1cf4d5cb4f2ba3de0e8f6b39c0a1".as_slice()),
+            ("PRIORITY", b"4"),
+            ("MESSAGE", b"Request to /index.html failed with 503"),
+            ("PATH", b"/index.html"),
+            ("STATUS", b"503"),
+            ("REQUEST_ID", b"12"),
+        ]
+    );
+
+    let missing = RequestFailed {
+        path: "/about.html",
+        status: 404,
+        id: None,
+        body: Vec::new(),
+    };
+    let entry = missing.to_entry();
+    let id = journaled::raw::Field::validate("REQUEST_ID").unwrap();
+    assert_eq!(entry.get(id), None);
+    assert_eq!(entry.len(), 5);
+
+    let journal = journaled::raw::JournalWriter::new().expect("new failed");
+    request.send(&journal).expect("send failed");
+    missing.send(&journal).expect("send failed");
+}
@@ -0,0 +1,24 @@
+#[cfg(feature = "stdlog")]
+#[test]
+fn test_init_with() {
+    let builder = journaled::log::Builder::new()
+        .default_level(log::LevelFilter::Warn)
+        .parse_filters("log_filter::verbose=trace")
+        .expect("invalid filters");
+    let handle = journaled::log::init_with(builder).expect("unable to initialize logger");
+    // Only one logger can be installed, so a second one is an error rather than ignored.
+    assert!(journaled::log::init_with(journaled::log::Builder::new()).is_err());
+
+    assert!(!log::log_enabled!(log::Level::Info));
+    assert!(log::log_enabled!(target: "log_filter::verbose", log::Level::Trace));
+    log::warn!("Warn");
+
+    handle.set_level("log_filter", log::LevelFilter::Debug);
+    assert!(log::log_enabled!(log::Level::Debug));
+    assert!(!log::log_enabled!(log::Level::Trace));
+    log::debug!("Debug");
+
+    handle.set_filter("error".parse().expect("invalid filter"));
+    assert!(!log::log_enabled!(log::Level::Warn));
+    assert_eq!(log::max_level(), log::LevelFilter::Error);
+}
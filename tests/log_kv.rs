@@ -0,0 +1,7 @@
+#[cfg(feature = "stdlog-kv")]
+#[test]
+fn test_write_key_values() {
+    journaled::log::init().expect("unable to initialize logger");
+
+    log::info!(user_id = 42, request = "GET /"; "Key Values");
+}
@@ -0,0 +1,20 @@
+#[cfg(feature = "stdlog")]
+#[test]
+fn test_update_with_priorities() {
+    let builder = journaled::log::Builder::new()
+        .priorities(journaled::log::PriorityMap::default().discard(log::Level::Trace))
+        .parse_filters("log_priorities::verbose=trace")
+        .expect("invalid filters");
+    let handle = journaled::log::init_with(builder).expect("unable to initialize logger");
+    // Trace records are discarded, so the max level stops at debug however the filter changes.
+    assert_eq!(log::max_level(), log::LevelFilter::Debug);
+
+    handle.set_level("log_priorities::verbose", log::LevelFilter::Warn);
+    assert_eq!(log::max_level(), log::LevelFilter::Info);
+
+    handle.set_level("log_priorities::verbose", log::LevelFilter::Trace);
+    assert_eq!(log::max_level(), log::LevelFilter::Debug);
+    assert!(!log::log_enabled!(target: "log_priorities::verbose", log::Level::Trace));
+    assert!(log::log_enabled!(target: "log_priorities::verbose", log::Level::Debug));
+    log::debug!(target: "log_priorities::verbose", "Debug");
+}
@@ -0,0 +1,11 @@
+#[test]
+fn test_install_hook() {
+    journaled::panic::install_hook().expect("install failed");
+
+    let result = std::thread::Builder::new()
+        .name("panicking".to_owned())
+        .spawn(|| panic!("test panic {}", 1))
+        .expect("spawn failed")
+        .join();
+    assert!(result.is_err());
+}
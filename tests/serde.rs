@@ -0,0 +1,25 @@
+#[cfg(feature = "serde")]
+#[test]
+fn test_write() {
+    #[derive(serde::Serialize)]
+    struct Request<'a> {
+        message: &'a str,
+        path: &'a str,
+        tags: &'a [&'a str],
+        user: User,
+    }
+
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u64,
+    }
+
+    let journal = journaled::raw::JournalWriter::new().expect("new failed");
+    let request = Request {
+        message: "Hello Serde",
+        path: "/index.html",
+        tags: &["cached", "gzip"],
+        user: User { id: 12 },
+    };
+    journaled::serde::send(&journal, &request).expect("send failed");
+}
@@ -0,0 +1,19 @@
+#[cfg(feature = "slog")]
+#[test]
+fn test_write() {
+    use journaled::raw::Priority;
+
+    let priorities = journaled::slog::PriorityMap::default()
+        .map(slog::Level::Info, Priority::Notice)
+        .discard(slog::Level::Trace);
+    let drain = journaled::slog::JournalDrain::new()
+        .expect("new failed")
+        .with_level(slog::Level::Debug)
+        .with_priorities(priorities)
+        .with_key_prefix(journaled::raw::Field::validate("TEST_").expect("invalid prefix"));
+    let logger = slog::Logger::root(slog::Drain::fuse(drain), slog::o!("service" => "test"));
+
+    slog::info!(logger, "Info"; "request" => 7, "cached" => true);
+    slog::info!(logger, "Info"; "request" => 8, "bytes" => b"\0\x01".as_slice());
+    slog::trace!(logger, "Trace");
+}
@@ -0,0 +1,33 @@
+#[cfg(feature = "tracing")]
+#[test]
+fn test_write() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let layer = journaled::tracing::JournalLayer::new()
+        .expect("new failed")
+        .with_field_prefix(journaled::raw::Field::validate("TEST_").expect("invalid prefix"))
+        .with_span_close(true);
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "request",
+            id = 7,
+            user = tracing::field::Empty,
+            code.func = "test_write",
+        );
+        let _guard = span.enter();
+        span.record("user", "test");
+
+        tracing::info!(cached = true, "Info");
+        tracing::debug_span!("query").in_scope(|| tracing::warn!(rows = 0, "Warning"));
+
+        tracing::dispatcher::get_default(|dispatch| {
+            dispatch
+                .downcast_ref::<journaled::tracing::JournalLayer>()
+                .expect("layer not found")
+                .flush()
+                .expect("flush failed")
+        });
+    });
+}
@@ -7,3 +7,98 @@ fn test_write() {
         .send([(journaled::raw::MESSAGE, "Hello World")].into_iter())
         .expect("send failed");
 }
+
+journaled::message_id! {
+    TEST_MESSAGE = "a2a5ac4c5b6e4ffa9a4df4bdf4bb1c42";
+}
+
+#[test]
+fn test_write_message_id() {
+    let journal = journaled::raw::JournalWriter::new().expect("new failed");
+
+    journal
+        .send(
+            [
+                (journaled::raw::MESSAGE, "Hello World"),
+                TEST_MESSAGE.as_value(),
+            ]
+            .into_iter(),
+        )
+        .expect("send failed");
+    journal
+        .send([(journaled::raw::MESSAGE_ID, TEST_MESSAGE)].into_iter())
+        .expect("send failed");
+}
+
+#[test]
+fn test_write_facility() {
+    let journal = journaled::raw::JournalWriter::new()
+        .expect("new failed")
+        .with_facility(journaled::raw::Facility::Local3);
+    assert_eq!(journal.facility(), Some(journaled::raw::Facility::Local3));
+
+    journal
+        .send([(journaled::raw::MESSAGE, "Hello Facility")].into_iter())
+        .expect("send failed");
+}
+
+#[test]
+fn test_write_location() {
+    let journal = journaled::raw::JournalWriter::new().expect("new failed");
+    let location = journaled::location!();
+
+    journal
+        .send(
+            [(journaled::raw::MESSAGE, "Hello Location")]
+                .into_iter()
+                .chain(location.as_values()),
+        )
+        .expect("send failed");
+}
+
+#[test]
+fn test_write_error() {
+    let journal = journaled::raw::JournalWriter::new().expect("new failed");
+    let error = std::fs::read("/nonexistent/journaled").expect_err("read succeeded");
+
+    journaled::error::send(&journal, &error).expect("send failed");
+}
+
+#[test]
+fn test_write_rate_limit() {
+    let limit = journaled::ratelimit::RateLimit::new(2, std::time::Duration::from_millis(10));
+    let journal = journaled::raw::JournalWriter::new()
+        .expect("new failed")
+        .with_rate_limit(limit);
+
+    let send = |message| {
+        let location = journaled::location!();
+        journal
+            .send(
+                [(journaled::raw::MESSAGE, message)]
+                    .into_iter()
+                    .chain(location.as_values()),
+            )
+            .expect("send failed");
+    };
+    for _ in 0..5 {
+        send("Hello Rate Limit");
+    }
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    send("Hello Again");
+}
+
+#[test]
+fn test_write_dedup() {
+    let dedup = journaled::dedup::Dedup::new(std::time::Duration::from_secs(60));
+    let journal = journaled::raw::JournalWriter::new()
+        .expect("new failed")
+        .with_dedup(dedup);
+
+    for _ in 0..5 {
+        journal
+            .send([(journaled::raw::MESSAGE, "Hello Dedup")].into_iter())
+            .expect("send failed");
+    }
+    journal.flush().expect("flush failed");
+}